@@ -22,12 +22,165 @@
 
 use super::*;
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(tag = "type", rename_all = "lowercase")]
+#[derive(Clone, Debug)]
 pub enum CargoMessage {
 	Suite(CargoTestReportSuite),
 	Test(CargoTestReportTest),
-	Bench(CargoTestReportBench)
+	Bench(CargoTestReportBench),
+	/// A message of cargo itself, with `--message-format=json`.
+	Build(clippy::Message),
+	/// One of cargo's `Running ...` lines, in output that includes cargo's STDERR.
+	Running(CargoTestBinary)
+}
+
+impl<'de> Deserialize<'de> for CargoMessage {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		#[derive(Deserialize)]
+		#[serde(tag = "type", rename_all = "lowercase")]
+		enum Libtest {
+			Suite(CargoTestReportSuite),
+			Test(CargoTestReportTest),
+			Bench(CargoTestReportBench)
+		}
+		
+		let value = serde_json::Value::deserialize(deserializer)?;
+		
+		if value.get("reason").is_some() {
+			return serde_json::from_value(value).map(Self::Build).map_err(de::Error::custom);
+		}
+		
+		Ok(match serde_json::from_value(value).map_err(de::Error::custom)? {
+			Libtest::Suite(v) => Self::Suite(v),
+			Libtest::Test(v)  => Self::Test(v),
+			Libtest::Bench(v) => Self::Bench(v)
+		})
+	}
+}
+
+impl Message for CargoMessage {
+	fn from_line(line: &str) -> Option<Self> {
+		CargoTestBinary::parse(line).map(Self::Running)
+	}
+}
+
+/// A test binary that cargo is about to run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CargoTestBinary {
+	/// `Running unittests src/lib.rs (target/debug/deps/my_crate-0123456789abcdef)`
+	Unittests {
+		src_path:   String,
+		executable: String
+	},
+	/// `Running tests/integration.rs (target/debug/deps/integration-0123456789abcdef)`, or only the
+	/// executable with older versions of cargo, or the command line in backticks with `--verbose`
+	Target {
+		src_path:   Option<String>,
+		executable: String
+	},
+	/// `Doc-tests my_crate`
+	Doctests {
+		crate_name: String
+	}
+}
+
+impl CargoTestBinary {
+	/// Parses one of cargo's `Running ...` or `Doc-tests ...` lines, which may be colored. The command lines of
+	/// the compiler, which `--verbose` prints as ``Running `rustc ...` ``, are not test binaries.
+	pub fn parse(line: &str) -> Option<Self> {
+		let line = strip_ansi(line);
+		let line = line.trim();
+		
+		if let Some(crate_name) = line.strip_prefix("Doc-tests ") {
+			return Some(Self::Doctests { crate_name: crate_name.trim().to_string() });
+		}
+		
+		let running = line.strip_prefix("Running ")?.trim();
+		
+		// `--verbose` prints the command line, with the environment, like ``Running `CARGO=cargo target/debug/deps/foo-0123 --format json` ``
+		if let Some(command) = running.strip_prefix('`').and_then(|v| v.strip_suffix('`')) {
+			let executable = shell_words(command)
+				.into_iter()
+				.find(|word| !is_assignment(word))?;
+			let program = std::path::Path::new(&executable).file_stem()?.to_str()?;
+			
+			return match program {
+				"rustc" | "rustdoc" | "clippy-driver" => None,
+				_ => Some(Self::Target { src_path: None, executable })
+			};
+		}
+		
+		Some(match running.strip_suffix(')').and_then(|v| v.rsplit_once(" (")) {
+			Some((target, executable)) => match target.strip_prefix("unittests ") {
+				Some(src_path) => Self::Unittests { src_path: src_path.to_string(), executable: executable.to_string() },
+				None           => Self::Target { src_path: Some(target.to_string()), executable: executable.to_string() }
+			},
+			None => Self::Target { src_path: None, executable: running.to_string() }
+		})
+	}
+	
+	/// Returns the executable of the test binary, doctests do not have one.
+	pub fn executable(&self) -> Option<&str> {
+		match self {
+			Self::Unittests { executable, .. } | Self::Target { executable, .. } => Some(executable),
+			Self::Doctests { .. } => None
+		}
+	}
+	
+	/// Returns the name of the target of the executable, without the hash cargo appends.
+	pub fn target_name(&self) -> Option<&str> {
+		match self {
+			Self::Unittests { executable, .. } | Self::Target { executable, .. } => {
+				let name = std::path::Path::new(executable).file_stem()?.to_str()?;
+				Some(name.rsplit_once('-').map_or(name, |(name, _)| name))
+			}
+			Self::Doctests { crate_name } => Some(crate_name)
+		}
+	}
+}
+
+/// Splits a command line into its words, like a shell, e.g. `A='b c' d` into `A=b c` and `d`.
+fn shell_words(command: &str) -> Vec<String> {
+	let mut words = Vec::new();
+	let mut word  = None::<String>;
+	let mut quote = None;
+	
+	for c in command.chars() {
+		match (quote, c) {
+			(Some(q), c) if c == q          => quote = None,
+			(Some(_), c)                    => word.get_or_insert_with(String::new).push(c),
+			(None, '\'' | '"')              => {
+				quote = Some(c);
+				word.get_or_insert_with(String::new);
+			}
+			(None, c) if c.is_whitespace()  => words.extend(word.take()),
+			(None, c)                       => word.get_or_insert_with(String::new).push(c)
+		}
+	}
+	
+	words.extend(word);
+	words
+}
+
+/// Returns whether a word of a command line sets an environment variable, e.g. `CARGO_PKG_NAME=foo`.
+fn is_assignment(word: &str) -> bool {
+	word.split_once('=').is_some_and(|(name, _)| !name.is_empty()
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+}
+
+/// Removes the SGR escape sequences of colored output.
+pub fn strip_ansi(line: &str) -> String {
+	let mut out   = String::with_capacity(line.len());
+	let mut chars = line.chars();
+	
+	while let Some(c) = chars.next() {
+		if c == '\x1b' {
+			chars.by_ref().find(|c| c.is_ascii_alphabetic());
+		} else {
+			out.push(c);
+		}
+	}
+	
+	out
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -35,12 +188,17 @@ pub enum CargoMessage {
 pub enum CargoTestReportSuite {
 	Started(CargoTestReportSuiteStarted),
 	Ok(CargoTestReportSuiteOkOrFailed),
-	Failed(CargoTestReportSuiteOkOrFailed)
+	Failed(CargoTestReportSuiteOkOrFailed),
+	/// An event of a newer version of libtest.
+	#[serde(other)]
+	Unknown
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct CargoTestReportSuiteStarted {
-	pub test_count: usize
+	pub test_count: usize,
+	/// The test binary, only emitted by nextest with `--message-format libtest-json-plus`.
+	pub nextest:    Option<NextestSuite>
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -52,7 +210,50 @@ pub struct CargoTestReportSuiteOkOrFailed {
 	pub ignored:       usize,
 	pub measured:      usize,
 	pub filtered_out:  usize,
-	pub exec_time:     f64
+	pub exec_time:     f64,
+	pub nextest:       Option<NextestSuite>
+}
+
+/// The test binary of a suite, as emitted by nextest.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NextestSuite {
+	#[serde(rename = "crate")]
+	pub crate_name:  String,
+	pub test_binary: String,
+	pub kind:        String
+}
+
+impl NextestSuite {
+	/// Parses the id nextest prefixes the names of the tests of a binary with, the inverse of [`Self::binary_id`],
+	/// a proc-macro is taken for a library.
+	pub fn from_binary_id(id: &str) -> Self {
+		let (crate_name, rest) = id.split_once("::").unwrap_or((id, ""));
+		let (kind, test_binary) = match rest.split_once('/') {
+			Some((kind, name))      => (kind, name.to_string()),
+			None if rest.is_empty() => ("lib", crate_name.replace('-', "_")),
+			None                    => ("test", rest.to_string())
+		};
+		
+		Self { crate_name: crate_name.to_string(), test_binary, kind: kind.to_string() }
+	}
+	
+	/// Returns the id nextest prefixes the names of the tests of this binary with, e.g. `my-crate::integration`.
+	pub fn binary_id(&self) -> String {
+		match self.kind.as_str() {
+			"lib" | "proc-macro" => self.crate_name.clone(),
+			"test"               => format!("{}::{}", self.crate_name, self.test_binary),
+			kind                 => format!("{}::{}/{}", self.crate_name, kind, self.test_binary)
+		}
+	}
+	
+	/// Returns the name of the suite, like the names of [`CargoTestBinary`] suites.
+	pub fn suite_name(&self) -> String {
+		match self.kind.as_str() {
+			"lib" | "proc-macro" => format!("{}::lib (unittests)", self.crate_name),
+			"bin"                => format!("{}::bin/{} (unittests)", self.crate_name, self.test_binary),
+			_                    => self.binary_id()
+		}
+	}
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -66,19 +267,279 @@ pub struct CargoTestReportTest {
 #[serde(tag = "event", rename_all = "lowercase")]
 pub enum CargoTestReportTestEvent {
 	Started,
-	Ignored,
+	Ignored(CargoTestReportTestIgnored),
 	Ok(CargoTestReportTestOkOrFailed),
-	Failed(CargoTestReportTestOkOrFailed)
+	Failed(CargoTestReportTestOkOrFailed),
+	/// The test runs for over 60 seconds, it still finishes with one of the events above.
+	Timeout,
+	/// An event of a newer version of libtest.
+	#[serde(other)]
+	Unknown
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct CargoTestReportTestOkOrFailed {
-	pub stdout: Option<String>
+	/// The duration of the test in seconds, only emitted with `--report-time`.
+	pub exec_time: Option<f64>,
+	pub stdout:    Option<String>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CargoTestReportTestIgnored {
+	/// The reason of `#[ignore = "reason"]`.
+	pub message: Option<String>
+}
+
+/// A doctest, named like `src/lib.rs - parser::Parser::parse (line 120)`, or `src/lib.rs - (line 1)` for
+/// the documentation of the crate.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Doctest {
+	/// The file of the documented item, relative to the package.
+	pub file: String,
+	/// The path of the documented item.
+	pub item: Option<String>,
+	/// The line the code block starts at.
+	pub line: usize
+}
+
+impl Doctest {
+	pub fn parse(name: &str) -> Option<Self> {
+		let (file, rest) = name.split_once(" - ")?;
+		// attributes like `compile fail` may follow the line
+		let start = rest.rfind("(line ")?;
+		let end   = start + rest[start..].find(')')?;
+		let line  = rest[start + "(line ".len()..end].parse().ok()?;
+		let item  = rest[..start].trim();
+		
+		Some(Self {
+			file: file.to_string(),
+			item: Some(item.to_string()).filter(|v| !v.is_empty()),
+			line
+		})
+	}
+}
+
+impl CargoTestReportTestOkOrFailed {
+	/// Parses the message and location of the first panic in the captured output.
+	pub fn panic(&self) -> Option<Panic> {
+		Panic::parse(self.stdout.as_deref()?)
+	}
+}
+
+/// A panic, as printed by the default panic hook.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Panic {
+	pub message: String,
+	pub file:    String,
+	pub line:    usize,
+	pub column:  usize
+}
+
+impl Panic {
+	/// Parses the first panic in the output of a test, in either the format before Rust 1.73:
+	///
+	/// ```text
+	/// thread 'tests::foo' panicked at 'assertion failed: x', src/foo.rs:42:5
+	/// ```
+	///
+	/// or the format since, where the message may span multiple lines:
+	///
+	/// ```text
+	/// thread 'tests::foo' (1234) panicked at src/foo.rs:42:5:
+	/// assertion failed: x
+	/// ```
+	pub fn parse(output: &str) -> Option<Self> {
+		const PANICKED_AT: &str = " panicked at ";
+		
+		let rest = &output[output.find(PANICKED_AT)? + PANICKED_AT.len()..];
+		
+		if let Some(rest) = rest.strip_prefix('\'') {
+			// the message may contain `', ` itself, so the first one that is followed by only a location ends it
+			return rest.match_indices("', ").find_map(|(i, _)| {
+				let location = rest[i + 3..].lines().next().filter(|v| !v.contains("', "))?;
+				let (file, line, column) = parse_location(location)?;
+				Some(Self { message: rest[..i].to_string(), file, line, column })
+			});
+		}
+		
+		let (location, message) = rest.split_once('\n').unwrap_or((rest, ""));
+		let (file, line, column) = parse_location(location.trim_end().strip_suffix(':')?)?;
+		let message = message.lines()
+			.take_while(|line| !line.starts_with("note: ") && !line.starts_with("stack backtrace:"))
+			.collect::<Vec<_>>()
+			.join("\n");
+		
+		Some(Self { message: message.trim_end().to_string(), file, line, column })
+	}
+}
+
+/// Parses a `file:line:column` location.
+fn parse_location(location: &str) -> Option<(String, usize, usize)> {
+	let mut parts = location.trim().rsplitn(3, ':');
+	let column = parts.next()?.parse().ok()?;
+	let line   = parts.next()?.parse().ok()?;
+	let file   = parts.next().filter(|v| !v.is_empty())?;
+	Some((file.to_string(), line, column))
 }
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct CargoTestReportBench {
-	pub name:      String,
-	pub median:    f64,
-	pub deviation: f64
-}
\ No newline at end of file
+	pub name:           String,
+	pub median:         f64,
+	pub deviation:      f64,
+	/// The throughput, only emitted for benchmarks that set `Bencher::bytes`.
+	pub mib_per_second: Option<f64>
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn doctest(file: &str, item: Option<&str>, line: usize) -> Option<Doctest> {
+		Some(Doctest { file: file.to_string(), item: item.map(str::to_string), line })
+	}
+	
+	#[test]
+	fn parse_doctest() {
+		assert_eq!(
+			Doctest::parse("src/lib.rs - parser::Parser::parse (line 120)"),
+			doctest("src/lib.rs", Some("parser::Parser::parse"), 120));
+		assert_eq!(
+			Doctest::parse("src/lib.rs - Wrapper<T>::new (line 7)"),
+			doctest("src/lib.rs", Some("Wrapper<T>::new"), 7));
+		assert_eq!(Doctest::parse("src/lib.rs - (line 1)"), doctest("src/lib.rs", None, 1));
+	}
+	
+	#[test]
+	fn parse_doctest_attributes() {
+		assert_eq!(
+			Doctest::parse("src/lib.rs - parser::parse (line 42) - compile fail"),
+			doctest("src/lib.rs", Some("parser::parse"), 42));
+		assert_eq!(
+			Doctest::parse("src/lib.rs - (line 3) - compile"),
+			doctest("src/lib.rs", None, 3));
+	}
+	
+	#[test]
+	fn parse_doctest_none() {
+		assert_eq!(Doctest::parse("tests::parse"), None);
+		assert_eq!(Doctest::parse("src/lib.rs - parse"), None);
+		assert_eq!(Doctest::parse("src/lib.rs - parse (line x)"), None);
+	}
+	
+	fn panic(message: &str, file: &str, line: usize, column: usize) -> Option<Panic> {
+		Some(Panic { message: message.to_string(), file: file.to_string(), line, column })
+	}
+	
+	#[test]
+	fn parse_panic_old_format() {
+		assert_eq!(
+			Panic::parse("running 1 test\nthread 'tests::foo' panicked at 'assertion failed: x', src/foo.rs:42:5\nnote: run with `RUST_BACKTRACE=1`\n"),
+			panic("assertion failed: x", "src/foo.rs", 42, 5));
+		assert_eq!(
+			Panic::parse("thread 'tests::foo' panicked at 'expected `a', got `b`', src/foo.rs:7:9\n"),
+			panic("expected `a', got `b`", "src/foo.rs", 7, 9));
+		assert_eq!(
+			Panic::parse("thread 'tests::foo' panicked at 'assertion failed: `(left == right)`\n  left: `1`,\n right: `2`', src/foo.rs:3:5\n"),
+			panic("assertion failed: `(left == right)`\n  left: `1`,\n right: `2`", "src/foo.rs", 3, 5));
+	}
+	
+	#[test]
+	fn parse_panic_new_format() {
+		assert_eq!(
+			Panic::parse("\nthread 'tests::foo' panicked at src/foo.rs:42:5:\nassertion `left == right` failed\n  left: 1\n right: 2\nnote: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n"),
+			panic("assertion `left == right` failed\n  left: 1\n right: 2", "src/foo.rs", 42, 5));
+		assert_eq!(
+			Panic::parse("thread 'tests::foo' (1234) panicked at tests/integration.rs:3:5:\nnope\nstack backtrace:\n   0: std::panicking::begin_panic\n"),
+			panic("nope", "tests/integration.rs", 3, 5));
+		assert_eq!(
+			Panic::parse("thread 'main' panicked at /rustc/59807616e1fa2540724bfbac14d7976d7e4a3860/library/core/src/option.rs:1:2:\ncalled `Option::unwrap()` on a `None` value"),
+			panic("called `Option::unwrap()` on a `None` value", "/rustc/59807616e1fa2540724bfbac14d7976d7e4a3860/library/core/src/option.rs", 1, 2));
+	}
+	
+	#[test]
+	fn parse_panic_none() {
+		assert_eq!(Panic::parse(""), None);
+		assert_eq!(Panic::parse("error: test failed\n"), None);
+		assert_eq!(Panic::parse("thread 'tests::foo' panicked at somewhere\n"), None);
+	}
+	
+	#[test]
+	fn parse_running_unittests() {
+		assert_eq!(
+			CargoTestBinary::parse("     Running unittests src/lib.rs (target/debug/deps/my_crate-0123456789abcdef)"),
+			Some(CargoTestBinary::Unittests {
+				src_path:   "src/lib.rs".to_string(),
+				executable: "target/debug/deps/my_crate-0123456789abcdef".to_string()
+			}));
+	}
+	
+	#[test]
+	fn parse_running_target() {
+		assert_eq!(
+			CargoTestBinary::parse("\x1b[1m\x1b[92m     Running\x1b[0m tests/integration.rs (target/debug/deps/integration-0123456789abcdef)"),
+			Some(CargoTestBinary::Target {
+				src_path:   Some("tests/integration.rs".to_string()),
+				executable: "target/debug/deps/integration-0123456789abcdef".to_string()
+			}));
+		assert_eq!(
+			CargoTestBinary::parse("     Running target/debug/deps/integration-0123456789abcdef"),
+			Some(CargoTestBinary::Target {
+				src_path:   None,
+				executable: "target/debug/deps/integration-0123456789abcdef".to_string()
+			}));
+	}
+	
+	#[test]
+	fn parse_doctests() {
+		assert_eq!(
+			CargoTestBinary::parse("   Doc-tests my_crate"),
+			Some(CargoTestBinary::Doctests { crate_name: "my_crate".to_string() }));
+	}
+	
+	#[test]
+	fn parse_running_verbose() {
+		let binary = CargoTestBinary::parse(
+			"     Running `/work/target/debug/deps/my_crate-0123456789abcdef -Z unstable-options --format json --report-time`");
+		assert_eq!(binary, Some(CargoTestBinary::Target {
+			src_path:   None,
+			executable: "/work/target/debug/deps/my_crate-0123456789abcdef".to_string()
+		}));
+		assert_eq!(binary.as_ref().and_then(CargoTestBinary::target_name), Some("my_crate"));
+		
+		assert_eq!(
+			CargoTestBinary::parse("     Running `CARGO=/usr/bin/cargo CARGO_PKG_DESCRIPTION='a crate' /work/target/debug/deps/integration-0123456789abcdef --format json`"),
+			Some(CargoTestBinary::Target {
+				src_path:   None,
+				executable: "/work/target/debug/deps/integration-0123456789abcdef".to_string()
+			}));
+	}
+	
+	#[test]
+	fn parse_running_verbose_compiler() {
+		assert_eq!(CargoTestBinary::parse("     Running `rustc --crate-name my_crate --edition=2018 src/lib.rs`"), None);
+		assert_eq!(CargoTestBinary::parse(
+			"     Running `CARGO=/usr/bin/cargo /root/.rustup/toolchains/stable/bin/rustdoc --edition=2018 --crate-name my_crate --test src/lib.rs --check-cfg 'cfg(docsrs,test)'`"),
+			None);
+	}
+	
+	#[test]
+	fn nextest_binary_id() {
+		for (id, kind, suite) in [
+			("my-crate", "lib", "my-crate::lib (unittests)"),
+			("my-crate::integration", "test", "my-crate::integration"),
+			("my-crate::bin/my-bin", "bin", "my-crate::bin/my-bin (unittests)")
+		] {
+			let nextest = NextestSuite::from_binary_id(id);
+			assert_eq!(nextest.kind, kind);
+			assert_eq!(nextest.binary_id(), id);
+			assert_eq!(nextest.suite_name(), suite);
+		}
+	}
+	
+	#[test]
+	fn parse_other_lines() {
+		assert_eq!(CargoTestBinary::parse("   Compiling my_crate v0.1.0 (/work)"), None);
+		assert_eq!(CargoTestBinary::parse("error: test failed, to rerun pass `--lib`"), None);
+	}
+}
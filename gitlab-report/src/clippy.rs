@@ -27,7 +27,7 @@ use super::*;
 #[serde(tag = "reason", rename_all = "kebab-case")]
 pub enum Message {
 	CompilerMessage(CompilerMessage),
-	CompilerArtifact,
+	CompilerArtifact(CompilerArtifact),
 	BuildFinished,
 	BuildScriptExecuted,
 	#[serde(other)]
@@ -36,7 +36,9 @@ pub enum Message {
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct CompilerMessage {
-	pub message: CompilerMessageMessage
+	#[serde(default)]
+	pub package_id: Option<String>,
+	pub message:    CompilerMessageMessage
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -61,6 +63,8 @@ pub struct CompilerMessageMessageSpan {
 	pub line_end:     usize,
 	pub column_start: usize,
 	pub column_end:   usize,
+	#[serde(default)]
+	pub is_primary:   bool
 }
 
 impl Into<code_climate::CodeQualityReportLocation> for CompilerMessageMessageSpan {
@@ -80,4 +84,40 @@ impl Into<code_climate::CodeQualityReportLocation> for CompilerMessageMessageSpa
 			})
 		}
 	}
-}
\ No newline at end of file
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompilerArtifact {
+	pub package_id:    String,
+	#[serde(default)]
+	pub manifest_path: Option<String>,
+	pub target:        CompilerArtifactTarget,
+	pub executable:    Option<String>
+}
+
+impl CompilerArtifact {
+	/// Returns the name of the package, from either the old `name version (source)` or the newer
+	/// `source#name@version` (or `source#version`, if the name is the last segment of the source) package ids.
+	pub fn package_name(&self) -> &str {
+		package_name(&self.package_id)
+	}
+}
+
+/// Returns the name of the package of a package id, see [`CompilerArtifact::package_name`].
+pub fn package_name(id: &str) -> &str {
+	match id.split_once(' ') {
+		Some((name, _)) => name,
+		None => match id.rsplit_once('#') {
+			Some((_, fragment)) if fragment.contains('@') => fragment.split('@').next().unwrap_or(fragment),
+			Some((source, _)) => source.trim_end_matches('/').rsplit('/').next().unwrap_or(source),
+			None => id
+		}
+	}
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CompilerArtifactTarget {
+	pub name:     String,
+	pub kind:     Vec<String>,
+	pub src_path: String
+}
@@ -29,7 +29,19 @@ use super::*;
 pub const CODE_QUALITY_REPORT_TYPE: &str = "issue";
 
 #[derive(Clone, Debug, Serialize)]
-pub struct CodeQualityReport(Vec<CodeQualityReportIssue>);
+pub struct CodeQualityReport(pub Vec<CodeQualityReportIssue>);
+
+impl WriteReport for CodeQualityReport {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		serde_json::to_writer(writer, self).map_err(io::Error::from)
+	}
+}
+
+impl Findings for CodeQualityReport {
+	fn findings(&self) -> usize {
+		self.0.len()
+	}
+}
 
 #[derive(Clone, Debug, Serialize)]
 pub struct CodeQualityReportIssue {
@@ -58,7 +70,7 @@ pub enum CodeQualityReportIssueCategory {
 	Style
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CodeQualityReportIssueSeverity {
 	Info,
@@ -0,0 +1,188 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Preparation of cargo commands, so that they emit the JSON output the converters expect.
+
+use {super::*, detect::InputFormat};
+
+/// Global cargo options that take a value, used to find the subcommand.
+const GLOBAL_OPTIONS_WITH_VALUE: &[&str] = &["--color", "--config", "-C", "-Z"];
+
+/// Returns the position of the cargo subcommand in the arguments of a cargo command (without the program)
+/// and the input format of its output, e.g. `Test` for `+nightly --verbose test --workspace`. The position of
+/// `cargo nextest run` is the one of `run`.
+pub fn subcommand(args: &[String]) -> Option<(usize, InputFormat)> {
+	let mut i = 0;
+	
+	while let Some(arg) = args.get(i) {
+		match arg.as_str() {
+			v if GLOBAL_OPTIONS_WITH_VALUE.contains(&v) => i += 2,
+			v if v.starts_with('-') || v.starts_with('+') => i += 1,
+			"test" | "t"  => return Some((i, InputFormat::Test)),
+			"nextest"     => return match args.get(i + 1).map(String::as_str) {
+				Some("run" | "r") => Some((i + 1, InputFormat::Nextest)),
+				_                 => None
+			},
+			"clippy"      => return Some((i, InputFormat::Clippy)),
+			"bench"       => return Some((i, InputFormat::Bench)),
+			"audit"       => return Some((i, InputFormat::Audit)),
+			"geiger"      => return Some((i, InputFormat::Geiger)),
+			_             => return None
+		}
+	}
+	
+	None
+}
+
+/// Returns the rustup toolchain of a cargo command (without the program), e.g. `nightly` for `+nightly test`.
+pub fn toolchain(args: &[String]) -> Option<&str> {
+	args.first()?.strip_prefix('+')
+}
+
+/// Adds the flags that enable JSON output to the arguments of a cargo command (without the program),
+/// returns the input format of its output. Flags that are already present are not added again.
+pub fn json_args(args: &[String]) -> Option<(InputFormat, Vec<String>)> {
+	let (i, format) = subcommand(args)?;
+	let mut args = args.to_vec();
+	let separator = args[i..].iter().position(|v| v == "--").map(|pos| i + pos);
+	let has = |args: &[String], flag: &str| args.iter().any(|v| v == flag || v.starts_with(&format!("{}=", flag)));
+	
+	match format {
+		InputFormat::Test | InputFormat::Bench => {
+			let harness_args = separator.map_or(&[][..], |pos| &args[pos + 1..]);
+			let mut extra = Vec::new();
+			
+			if !harness_args.windows(2).any(|v| v[0] == "-Z" && v[1] == "unstable-options") {
+				extra.extend(["-Z", "unstable-options"]);
+			}
+			
+			if !has(harness_args, "--format") {
+				extra.extend(["--format", "json"]);
+			}
+			
+			// per-test durations, otherwise they can only be estimated from the time the messages arrive
+			if format == InputFormat::Test && !has(harness_args, "--report-time") {
+				extra.push("--report-time");
+			}
+			
+			let pos = separator.unwrap_or(args.len());
+			
+			if separator.is_none() {
+				args.push("--".to_string());
+			}
+			
+			args.extend(extra.into_iter().map(str::to_string));
+			
+			// the artifact messages tell the packages and kinds of the test binaries, to name their suites
+			if format == InputFormat::Test && !has(&args[..pos], "--message-format") {
+				args.insert(pos, "--message-format=json".to_string());
+			}
+		}
+		InputFormat::Nextest => {
+			let nextest_args = &args[..separator.unwrap_or(args.len())];
+			
+			if !has(nextest_args, "--message-format") {
+				let pos = separator.unwrap_or(args.len());
+				args.splice(pos..pos, ["--message-format", "libtest-json-plus"].iter().map(|v| v.to_string()));
+			}
+		}
+		InputFormat::Clippy => {
+			let cargo_args = &args[..separator.unwrap_or(args.len())];
+			
+			if !has(cargo_args, "--message-format") {
+				let pos = separator.unwrap_or(args.len());
+				args.insert(pos, "--message-format=json".to_string());
+			}
+		}
+		InputFormat::Audit => if !has(&args, "--json") && !has(&args, "--format") {
+			args.push("--json".to_string());
+		},
+		InputFormat::Geiger => if !has(&args, "--output-format") {
+			args.extend(["--output-format", "Json"].iter().map(|v| v.to_string()));
+		},
+		InputFormat::Fmt => return None
+	}
+	
+	Some((format, args))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn json_args(args: &str) -> Option<(InputFormat, String)> {
+		let args = args.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+		super::json_args(&args).map(|(format, args)| (format, args.join(" ")))
+	}
+	
+	#[test]
+	fn subcommand_after_global_options() {
+		let args = |args: &str| args.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+		assert_eq!(subcommand(&args("+nightly --verbose test --workspace")), Some((2, InputFormat::Test)));
+		assert_eq!(subcommand(&args("--color always -Z unstable-options t")), Some((4, InputFormat::Test)));
+		assert_eq!(subcommand(&args("nextest r")), Some((1, InputFormat::Nextest)));
+		assert_eq!(subcommand(&args("nextest list")), None);
+		assert_eq!(subcommand(&args("build")), None);
+	}
+	
+	#[test]
+	fn json_args_test() {
+		assert_eq!(json_args("+nightly test --workspace"), Some((InputFormat::Test,
+			"+nightly test --workspace --message-format=json -- -Z unstable-options --format json --report-time".to_string())));
+		assert_eq!(json_args("test --no-fail-fast -- --include-ignored"), Some((InputFormat::Test,
+			"test --no-fail-fast --message-format=json -- --include-ignored -Z unstable-options --format json --report-time".to_string())));
+		assert_eq!(json_args("test --message-format json -- -Z unstable-options --format=json --report-time"), Some((InputFormat::Test,
+			"test --message-format json -- -Z unstable-options --format=json --report-time".to_string())));
+	}
+	
+	#[test]
+	fn json_args_bench() {
+		assert_eq!(json_args("bench"), Some((InputFormat::Bench, "bench -- -Z unstable-options --format json".to_string())));
+	}
+	
+	#[test]
+	fn json_args_nextest() {
+		assert_eq!(json_args("nextest run --retries 2"), Some((InputFormat::Nextest,
+			"nextest run --retries 2 --message-format libtest-json-plus".to_string())));
+		assert_eq!(json_args("nextest run -- filter"), Some((InputFormat::Nextest,
+			"nextest run --message-format libtest-json-plus -- filter".to_string())));
+		assert_eq!(json_args("nextest run --message-format libtest-json"), Some((InputFormat::Nextest,
+			"nextest run --message-format libtest-json".to_string())));
+	}
+	
+	#[test]
+	fn json_args_clippy() {
+		assert_eq!(json_args("clippy --all-targets -- -D warnings"), Some((InputFormat::Clippy,
+			"clippy --all-targets --message-format=json -- -D warnings".to_string())));
+		assert_eq!(json_args("clippy --message-format=json-diagnostic-rendered-ansi"), Some((InputFormat::Clippy,
+			"clippy --message-format=json-diagnostic-rendered-ansi".to_string())));
+	}
+	
+	#[test]
+	fn json_args_audit_geiger() {
+		assert_eq!(json_args("audit"), Some((InputFormat::Audit, "audit --json".to_string())));
+		assert_eq!(json_args("audit --format json"), Some((InputFormat::Audit, "audit --format json".to_string())));
+		assert_eq!(json_args("geiger --all-dependencies"), Some((InputFormat::Geiger,
+			"geiger --all-dependencies --output-format Json".to_string())));
+		assert_eq!(json_args("fmt"), None);
+	}
+}
@@ -0,0 +1,326 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Project configuration
+//!
+//! The configuration is read from a `.gitlab-report.toml` file, or the `[workspace.metadata.gitlab-report]`
+//! table of a `Cargo.toml` file, in the current directory or the closest parent directory that has one.
+//!
+//! ```toml
+//! input       = "cargo-output.json"
+//! path-prefix = "backend/"
+//!
+//! [outputs]
+//! test   = "results.xml"
+//!
+//! [test]
+//! attachments = ["**/snapshots/*{name}.snap.new"]
+//! output-head = 4096
+//! output-tail = 16384
+//!
+//! [labels]
+//! channel = "${CHANNEL}"
+//!
+//! [clippy]
+//! allow    = ["clippy::needless_return"]
+//! severity = { error = "blocker", warning = "major" }
+//!
+//! [audit]
+//! ignore   = ["RUSTSEC-2020-0071"]
+//! severity = { unmaintained = "low" }
+//! ```
+
+use {super::*, std::path::{Path, PathBuf}, detect::InputFormat};
+
+pub const CONFIG_FILE: &str = ".gitlab-report.toml";
+
+/// The number of bytes that are kept from the start and the end of the output of a test by default.
+pub const DEFAULT_OUTPUT_HEAD: usize = 32 * 1024;
+pub const DEFAULT_OUTPUT_TAIL: usize = 32 * 1024;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+	/// The input file, if none is specified on the command line.
+	pub input:       Option<String>,
+	/// The output file for each input format, if none is specified on the command line.
+	pub outputs:     HashMap<String, String>,
+	/// Labels added to every metric, `${VAR}` is replaced with the value of the environment variable.
+	pub labels:      HashMap<String, String>,
+	/// A prefix for the paths of source files, for workspaces that are not at the root of the repository.
+	pub path_prefix: Option<String>,
+	pub test:        TestConfig,
+	pub clippy:      ClippyConfig,
+	pub audit:       AuditConfig
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TestConfig {
+	/// Glob patterns of files that are attached to failing tests, `{name}` is replaced with the name of the test.
+	pub attachments: Vec<String>,
+	/// The number of bytes that are kept from the start of the output of a test, if it is too long.
+	pub output_head: Option<usize>,
+	/// The number of bytes that are kept from the end of the output of a test, if it is too long.
+	pub output_tail: Option<usize>
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ClippyConfig {
+	/// Lint codes that are not reported.
+	pub allow:    Vec<String>,
+	/// The severity for each diagnostic level, e.g. `warning`.
+	pub severity: HashMap<String, code_climate::CodeQualityReportIssueSeverity>
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct AuditConfig {
+	/// Advisories that are not reported.
+	pub ignore:   Vec<String>,
+	/// The severity for each kind of warning, e.g. `unmaintained`, or `vulnerability`.
+	pub severity: HashMap<String, gitlab_security_report::VulnerabilitySeverity>
+}
+
+impl Config {
+	/// Loads the configuration of the closest directory, starting at `dir`, that has one.
+	pub fn discover(dir: &Path) -> Result<Option<(PathBuf, Self)>, Error> {
+		for dir in dir.ancestors() {
+			let file = dir.join(CONFIG_FILE);
+			
+			if file.is_file() {
+				return Self::load(&file).map(|config| Some((file, config)));
+			}
+			
+			let file = dir.join("Cargo.toml");
+			
+			if file.is_file() {
+				if let Some(config) = Self::load_manifest(&file)? {
+					return Ok(Some((file, config)));
+				}
+			}
+		}
+		
+		Ok(None)
+	}
+	
+	/// Loads a `.gitlab-report.toml` file.
+	pub fn load(path: &Path) -> Result<Self, Error> {
+		toml::from_str::<Self>(&read(path)?)
+			.map_err(|e| Error::Config { path: path.to_path_buf(), message: e.to_string() })?
+			.check(path)
+	}
+	
+	/// Loads the `[workspace.metadata.gitlab-report]` table of a `Cargo.toml` file, if it has one.
+	pub fn load_manifest(path: &Path) -> Result<Option<Self>, Error> {
+		#[derive(Deserialize)]
+		struct Manifest {
+			workspace: Option<Workspace>
+		}
+		
+		#[derive(Deserialize)]
+		struct Workspace {
+			metadata: Option<Metadata>
+		}
+		
+		#[derive(Deserialize)]
+		struct Metadata {
+			#[serde(rename = "gitlab-report")]
+			gitlab_report: Option<Config>
+		}
+		
+		toml::from_str::<Manifest>(&read(path)?)
+			.map(|manifest| manifest.workspace
+				.and_then(|v| v.metadata)
+				.and_then(|v| v.gitlab_report))
+			.map_err(|e| Error::Config { path: path.to_path_buf(), message: e.to_string() })?
+			.map(|config| config.check(path))
+			.transpose()
+	}
+	
+	/// Compiles the attachment patterns, so that an invalid one is reported before the input is read.
+	fn check(self, path: &Path) -> Result<Self, Error> {
+		for pattern in &self.test.attachments {
+			glob::Pattern::new(&pattern.replace("{name}", "name")).map_err(|e| Error::Config {
+				path:    path.to_path_buf(),
+				message: format!("invalid attachment pattern `{}`: {}", pattern, e)
+			})?;
+		}
+		
+		Ok(self)
+	}
+	
+	/// Returns the configured output file of an input format.
+	pub fn output(&self, format: InputFormat) -> Option<&str> {
+		self.outputs.get(format.name()).map(String::as_str)
+	}
+	
+	/// Returns the labels with all environment variables replaced.
+	pub fn labels(&self) -> Vec<(String, String)> {
+		let mut labels = self.labels.iter()
+			.map(|(k, v)| (k.clone(), expand_env(v)))
+			.collect::<Vec<_>>();
+		labels.sort();
+		labels
+	}
+	
+	/// Returns the paths of the files that are attached to a failing test, `name` is the name of the test with its
+	/// module, e.g. `tests::parse`, `{name}` is replaced with the name without it. Patterns without `{name}` only
+	/// match files whose name contains the name with its module, with `::` or `__` between them, as e.g. insta
+	/// names its snapshots `<crate>__tests__parse.snap`.
+	pub fn attachments(&self, name: &str) -> Result<Vec<String>, Error> {
+		let short     = name.rsplit("::").next().unwrap_or(name);
+		let qualified = [name.to_string(), name.replace("::", "__")];
+		let mut paths = Vec::new();
+		
+		for pattern in &self.test.attachments {
+			let entries = glob::glob(&pattern.replace("{name}", &glob::Pattern::escape(short)))
+				.map_err(|e| Error::convert(format!("invalid attachment pattern `{}`: {}", pattern, e)))?;
+			
+			paths.extend(entries
+				.filter_map(Result::ok)
+				.filter(|path| path.is_file())
+				.filter(|path| pattern.contains("{name}") || path.file_name()
+					.is_some_and(|file| qualified.iter().any(|name| file.to_string_lossy().contains(name.as_str()))))
+				.map(|path| self.path(path.strip_prefix(".").unwrap_or(&path).to_string_lossy().as_ref())));
+		}
+		
+		paths.sort();
+		paths.dedup();
+		Ok(paths)
+	}
+	
+	/// Returns the number of bytes that are kept from the start and the end of the output of a test.
+	pub fn output_limits(&self) -> (usize, usize) {
+		(self.test.output_head.unwrap_or(DEFAULT_OUTPUT_HEAD), self.test.output_tail.unwrap_or(DEFAULT_OUTPUT_TAIL))
+	}
+	
+	/// Prepends the path prefix to a path of a source file.
+	pub fn path(&self, path: &str) -> String {
+		match &self.path_prefix {
+			Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), path),
+			None         => path.to_string()
+		}
+	}
+}
+
+fn read(path: &Path) -> Result<String, Error> {
+	std::fs::read_to_string(path).map_err(|e| Error::Config { path: path.to_path_buf(), message: e.to_string() })
+}
+
+/// Replaces `${VAR}` with the value of the environment variable `VAR`, or nothing, if it is not set.
+fn expand_env(value: &str) -> String {
+	let mut out  = String::with_capacity(value.len());
+	let mut rest = value;
+	
+	while let Some(start) = rest.find("${") {
+		let end = match rest[start..].find('}') {
+			Some(end) => start + end,
+			None      => break
+		};
+		
+		out.push_str(&rest[..start]);
+		out.push_str(&std::env::var(&rest[start + 2..end]).unwrap_or_default());
+		rest = &rest[end + 1..];
+	}
+	
+	out.push_str(rest);
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	/// A directory of its own for each test, which is removed when it is dropped.
+	struct TempDir(PathBuf);
+	
+	impl TempDir {
+		fn new(name: &str) -> Self {
+			let dir = std::env::temp_dir().join(format!("gitlab-report-{}-{}", name, std::process::id()));
+			let _   = std::fs::remove_dir_all(&dir);
+			std::fs::create_dir_all(&dir).unwrap();
+			Self(dir)
+		}
+		
+		fn write(&self, path: &str, content: &str) -> PathBuf {
+			let path = self.0.join(path);
+			std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+			std::fs::write(&path, content).unwrap();
+			path
+		}
+	}
+	
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = std::fs::remove_dir_all(&self.0);
+		}
+	}
+	
+	#[test]
+	fn discover_precedence() {
+		let dir = TempDir::new("discover");
+		dir.write("Cargo.toml", "[workspace]\nmembers = [\"app\"]\n\n[workspace.metadata.gitlab-report]\ninput = \"manifest.json\"\n");
+		dir.write("app/Cargo.toml", "[package]\nname = \"app\"\n");
+		
+		// a manifest without the table is skipped
+		let (path, config) = Config::discover(&dir.0.join("app")).unwrap().unwrap();
+		assert_eq!(path, dir.0.join("Cargo.toml"));
+		assert_eq!(config.input.as_deref(), Some("manifest.json"));
+		
+		let file = dir.write(CONFIG_FILE, "input = \"file.json\"\n");
+		let (path, config) = Config::discover(&dir.0.join("app")).unwrap().unwrap();
+		assert_eq!(path, file);
+		assert_eq!(config.input.as_deref(), Some("file.json"));
+	}
+	
+	#[test]
+	fn expand_env_vars() {
+		std::env::set_var("GITLAB_REPORT_TEST_CHANNEL", "nightly");
+		std::env::remove_var("GITLAB_REPORT_TEST_UNSET");
+		
+		assert_eq!(expand_env("${GITLAB_REPORT_TEST_CHANNEL}"), "nightly");
+		assert_eq!(expand_env("rust-${GITLAB_REPORT_TEST_CHANNEL}-${GITLAB_REPORT_TEST_CHANNEL}"), "rust-nightly-nightly");
+		assert_eq!(expand_env("rust-${GITLAB_REPORT_TEST_UNSET}"), "rust-");
+		assert_eq!(expand_env("$GITLAB_REPORT_TEST_CHANNEL ${GITLAB_REPORT_TEST_CHANNEL"), "$GITLAB_REPORT_TEST_CHANNEL ${GITLAB_REPORT_TEST_CHANNEL");
+		
+		let config = toml::from_str::<Config>("[labels]\nchannel = \"${GITLAB_REPORT_TEST_CHANNEL}\"\nrunner = \"${GITLAB_REPORT_TEST_UNSET}\"\n").unwrap();
+		assert_eq!(config.labels(), [
+			("channel".to_string(), "nightly".to_string()),
+			("runner".to_string(),  String::new())
+		]);
+	}
+	
+	#[test]
+	fn invalid_attachment_pattern() {
+		let dir  = TempDir::new("check");
+		let file = dir.write(CONFIG_FILE, "[test]\nattachments = [\"snapshots/[{name}.snap\"]\n");
+		assert!(matches!(Config::load(&file), Err(Error::Config { path, message }) if path == file && message.contains("snapshots/[{name}.snap")));
+		
+		let file = dir.write("Cargo.toml", "[workspace.metadata.gitlab-report.test]\nattachments = [\"***.snap\"]\n");
+		assert!(matches!(Config::load_manifest(&file), Err(Error::Config { .. })));
+		
+		let file = dir.write(CONFIG_FILE, "[test]\nattachments = [\"**/snapshots/*{name}.snap.new\"]\n");
+		assert!(Config::load(&file).is_ok());
+	}
+}
@@ -0,0 +1,295 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Detection of the tool that produced an input.
+
+use {super::*, std::io::Read, serde_json::Value};
+
+/// Maximum number of bytes that are peeked at to detect the input format, after cargo's last message.
+const MAX_PEEK: usize = 1 << 20;
+
+/// The tool that produced an input.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum InputFormat {
+	Test,
+	Nextest,
+	Clippy,
+	Bench,
+	Audit,
+	Geiger,
+	Fmt
+}
+
+impl InputFormat {
+	pub const ALL: [Self; 7] = [Self::Test, Self::Nextest, Self::Clippy, Self::Bench, Self::Audit, Self::Geiger, Self::Fmt];
+	
+	/// Returns the name of the input format, as used on the command line and in the configuration.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Test    => "test",
+			Self::Nextest => "nextest",
+			Self::Clippy  => "clippy",
+			Self::Bench   => "bench",
+			Self::Audit   => "audit",
+			Self::Geiger  => "geiger",
+			Self::Fmt     => "fmt"
+		}
+	}
+	
+	/// Returns the file name the default report of this input is usually written to.
+	pub fn default_output_file(self) -> Option<&'static str> {
+		match self {
+			Self::Test    => Some("results.xml"),
+			Self::Nextest => Some("results.xml"),
+			Self::Clippy  => Some("gl-code-quality-report.json"),
+			Self::Bench   => Some("metrics.txt"),
+			Self::Audit   => Some("gl-sast-report.json"),
+			Self::Geiger  => Some("gl-sast-report.json"),
+			Self::Fmt     => None
+		}
+	}
+}
+
+impl std::str::FromStr for InputFormat {
+	type Err = String;
+	
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL.iter()
+			.copied()
+			.find(|format| format.name() == s)
+			.ok_or_else(|| format!("invalid input format `{}`, expected one of `test`, `nextest`, `clippy`, `bench`, `audit`, `geiger` or `fmt`", s))
+	}
+}
+
+/// The input after it has been peeked at, yields the peeked part first.
+pub type Peeked<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Peeks at the first JSON values of the input to detect the tool that produced it.
+///
+/// libtest's output is detected as benchmark output as soon as a benchmark result is found, and as test output
+/// as soon as a suite, in which tests ran, finishes without one. cargo-nextest's output is detected by the names
+/// of its test binaries, which libtest does not emit. cargo's own messages precede libtest's output with
+/// `cargo test --message-format=json`, so they are only detected as clippy output, if cargo finishes the build
+/// without a test executable. The returned reader yields the complete input, including the peeked part.
+pub fn detect<R: io::BufRead>(reader: R) -> io::Result<(Option<InputFormat>, Peeked<R>)> {
+	detect_shared(reader, &std::sync::Mutex::new(Detector::default()))
+}
+
+/// Like [`detect`], but with a detector that another thread can take the peeked part from, if it cannot wait for
+/// the next line, e.g. when the process is terminated.
+pub fn detect_shared<R: io::BufRead>(mut reader: R, detector: &std::sync::Mutex<Detector>) -> io::Result<(Option<InputFormat>, Peeked<R>)> {
+	let lock = || detector.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+	let mut line = Vec::new();
+	
+	let format = loop {
+		line.clear();
+		
+		// the detector is not locked while the next line is awaited
+		let eof = reader.read_until(b'\n', &mut line)? == 0;
+		let mut detector = lock();
+		
+		if eof {
+			break detector.guess();
+		}
+		
+		if let Some(format) = detector.push(&line) {
+			break Some(format);
+		}
+		
+		if detector.buf.len() > detector.limit {
+			break detector.guess();
+		}
+	};
+	
+	Ok((format, io::Cursor::new(lock().take().unwrap_or_default()).chain(reader)))
+}
+
+/// The lines that were peeked at so far and what they tell about the input format.
+#[derive(Debug)]
+pub struct Detector {
+	buf:         Vec<u8>,
+	libtest:     bool,
+	cargo:       bool,
+	executables: bool,
+	limit:       usize,
+	taken:       bool
+}
+
+impl Default for Detector {
+	fn default() -> Self {
+		Self { buf: Vec::new(), libtest: false, cargo: false, executables: false, limit: MAX_PEEK, taken: false }
+	}
+}
+
+impl Detector {
+	/// Adds the next line of the input, returns the input format, if it is known now.
+	pub fn push(&mut self, line: &[u8]) -> Option<InputFormat> {
+		self.buf.extend_from_slice(line);
+		let value = serde_json::from_slice::<Value>(line).ok()?;
+		
+		match classify(&value) {
+			Some(InputFormat::Test) if suite_finished(&value) => return Some(InputFormat::Test),
+			Some(InputFormat::Test) => self.libtest = true,
+			Some(InputFormat::Clippy) => {
+				// cargo's messages of a large build may exceed the limit, but it always finishes the build
+				self.cargo = true;
+				self.limit = self.buf.len() + MAX_PEEK;
+				
+				match value.get("reason").and_then(Value::as_str) {
+					Some("compiler-artifact") => self.executables |= value.get("executable").is_some_and(|v| !v.is_null()),
+					Some("build-finished") if !self.executables => return Some(InputFormat::Clippy),
+					_ => ()
+				}
+			}
+			Some(format) => return Some(format),
+			None         => ()
+		}
+		
+		None
+	}
+	
+	/// Returns the most likely input format of the lines so far, at the end of the input or the peeked part.
+	pub fn guess(&self) -> Option<InputFormat> {
+		match (self.libtest || self.executables, self.cargo) {
+			(true, _)      => Some(InputFormat::Test),
+			(false, true)  => Some(InputFormat::Clippy),
+			// the input may be a single, pretty printed document
+			(false, false) => serde_json::from_slice::<Value>(&self.buf).ok().as_ref().and_then(classify)
+		}
+	}
+	
+	/// Takes the lines that were peeked at, unless they were already taken.
+	pub fn take(&mut self) -> Option<Vec<u8>> {
+		match std::mem::replace(&mut self.taken, true) {
+			true  => None,
+			false => Some(std::mem::take(&mut self.buf))
+		}
+	}
+}
+
+/// Returns whether the value is the end of a libtest suite, in which tests ran.
+///
+/// `cargo bench` runs the tests of a target with benchmarks as well, but ignores them, so a suite without
+/// passed or failed tests may still be followed by benchmarks.
+fn suite_finished(value: &Value) -> bool {
+	let count = |key| value.get(key).and_then(Value::as_u64).unwrap_or(0);
+	value.get("type").and_then(Value::as_str) == Some("suite")
+		&& matches!(value.get("event").and_then(Value::as_str), Some("ok" | "failed"))
+		&& count("passed") + count("failed") > 0
+}
+
+/// Detects the tool that produced a single JSON value.
+pub fn classify(value: &Value) -> Option<InputFormat> {
+	match value {
+		Value::Object(v) if v.contains_key("reason") => Some(InputFormat::Clippy),
+		Value::Object(v) if v.contains_key("vulnerabilities") && v.contains_key("database") => Some(InputFormat::Audit),
+		Value::Object(v) => match (v.get("type").and_then(Value::as_str), v.get("packages")) {
+			// nextest names the test binary of suites with `libtest-json-plus` and prefixes test names with it
+			(Some("suite"), _) if v.contains_key("nextest") => Some(InputFormat::Nextest),
+			(Some("test"), _) if v.get("name").and_then(Value::as_str)
+				.is_some_and(|name| name.contains('$')) => Some(InputFormat::Nextest),
+			(Some("suite" | "test"), _) => Some(InputFormat::Test),
+			(Some("bench"), _)          => Some(InputFormat::Bench),
+			(_, Some(Value::Array(packages))) if packages.iter()
+				.all(|package| package.get("unsafety").is_some()) => Some(InputFormat::Geiger),
+			_ => None
+		},
+		Value::Array(v) if v.iter()
+			.all(|file| file.get("name").is_some() && file.get("mismatches").is_some()) => Some(InputFormat::Fmt),
+		_ => None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn detect_str(input: &str) -> Option<InputFormat> {
+		detect(input.as_bytes()).unwrap().0
+	}
+	
+	#[test]
+	fn classify_values() {
+		let classify = |json: &str| classify(&serde_json::from_str(json).unwrap());
+		
+		assert_eq!(classify(r#"{"type":"suite","event":"started","test_count":1}"#), Some(InputFormat::Test));
+		assert_eq!(classify(r#"{"type":"test","event":"started","name":"tests::a"}"#), Some(InputFormat::Test));
+		assert_eq!(classify(r#"{"type":"test","event":"started","name":"my-crate$tests::a"}"#), Some(InputFormat::Nextest));
+		assert_eq!(classify(r#"{"type":"suite","event":"started","test_count":1,"nextest":{"crate":"my-crate","test_binary":"my_crate","kind":"lib"}}"#), Some(InputFormat::Nextest));
+		assert_eq!(classify(r#"{"type":"bench","name":"b","median":1,"deviation":0}"#), Some(InputFormat::Bench));
+		assert_eq!(classify(r#"{"reason":"compiler-message"}"#), Some(InputFormat::Clippy));
+		assert_eq!(classify(r#"{"database":{},"vulnerabilities":{}}"#), Some(InputFormat::Audit));
+		assert_eq!(classify(r#"{"packages":[{"unsafety":{}}]}"#), Some(InputFormat::Geiger));
+		assert_eq!(classify(r#"[{"name":"src/lib.rs","mismatches":[]}]"#), Some(InputFormat::Fmt));
+		assert_eq!(classify(r#"{"type":"other"}"#), None);
+		assert_eq!(classify(r#"42"#), None);
+	}
+	
+	#[test]
+	fn detect_tests_after_first_suite() {
+		// the input must not be read to its end, which is not valid JSON
+		let input = concat!(
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"test","event":"ok","name":"a"}"#, "\n",
+			r#"{"type":"suite","event":"ok","passed":1,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0}"#, "\n");
+		let (format, mut reader) = detect(input.as_bytes().chain(io::BufReader::new(io::repeat(b'x')))).unwrap();
+		let mut line = String::new();
+		
+		assert_eq!(format, Some(InputFormat::Test));
+		io::BufRead::read_line(&mut reader, &mut line).unwrap();
+		assert_eq!(line, "{\"type\":\"suite\",\"event\":\"started\",\"test_count\":1}\n");
+	}
+	
+	#[test]
+	fn detect_bench_after_ignored_tests() {
+		assert_eq!(detect_str(concat!(
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"test","event":"ignored","name":"a"}"#, "\n",
+			r#"{"type":"suite","event":"ok","passed":0,"failed":0,"ignored":1,"measured":0,"filtered_out":0,"exec_time":0}"#, "\n",
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"bench","name":"b","median":1,"deviation":0}"#, "\n"
+		)), Some(InputFormat::Bench));
+	}
+	
+	#[test]
+	fn detect_cargo_messages() {
+		let artifact = |executable: &str| format!(r#"{{"reason":"compiler-artifact","executable":{}}}"#, executable);
+		let finished = r#"{"reason":"build-finished","success":true}"#;
+		
+		assert_eq!(detect_str(&format!("{}\n{}\n", artifact("null"), finished)), Some(InputFormat::Clippy));
+		assert_eq!(detect_str(&format!("{}\n{}\n", artifact(r#""/target/debug/deps/a-0""#), finished)), Some(InputFormat::Test));
+		
+		// more than the limit of cargo's messages before the first test executable
+		let input = format!("{}{}\n{}\n",
+			format!("{}\n", artifact("null")).repeat(MAX_PEEK / 40),
+			artifact(r#""/target/debug/deps/a-0""#),
+			finished);
+		assert!(input.len() > MAX_PEEK);
+		assert_eq!(detect_str(&input), Some(InputFormat::Test));
+	}
+	
+	#[test]
+	fn detect_document() {
+		assert_eq!(detect_str("{\n  \"database\": {},\n  \"vulnerabilities\": {}\n}\n"), Some(InputFormat::Audit));
+		assert_eq!(detect_str("not json\n"), None);
+	}
+}
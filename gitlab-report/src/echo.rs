@@ -0,0 +1,156 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Human readable output of the consumed messages, for the job log.
+
+use super::*;
+
+/// A message that can be printed in a human readable form while it is consumed.
+pub trait Echo {
+	fn echo(&self, writer: &mut dyn io::Write) -> io::Result<()>;
+}
+
+impl Echo for cargo::CargoMessage {
+	fn echo(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		match self {
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Started(v)) =>
+				writeln!(writer, "\nrunning {} tests", v.test_count),
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Ok(v)) =>
+				echo_suite_result(writer, "\x1b[32mok\x1b[0m", v),
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Failed(v)) =>
+				echo_suite_result(writer, "\x1b[31mFAILED\x1b[0m", v),
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Unknown) => Ok(()),
+			cargo::CargoMessage::Test(v) => match &v.event {
+				cargo::CargoTestReportTestEvent::Started | cargo::CargoTestReportTestEvent::Unknown => Ok(()),
+				cargo::CargoTestReportTestEvent::Timeout   =>
+					writeln!(writer, "test {} has been running for over 60 seconds", v.name),
+				cargo::CargoTestReportTestEvent::Ignored(e) => match e.message.as_deref() {
+					Some(message) => writeln!(writer, "test {} ... \x1b[33mignored, {}\x1b[0m", v.name, message),
+					None          => writeln!(writer, "test {} ... \x1b[33mignored\x1b[0m", v.name)
+				},
+				cargo::CargoTestReportTestEvent::Ok(_)     =>
+					writeln!(writer, "test {} ... \x1b[32mok\x1b[0m", v.name),
+				cargo::CargoTestReportTestEvent::Failed(e) => {
+					writeln!(writer, "test {} ... \x1b[31mFAILED\x1b[0m", v.name)?;
+					
+					match e.stdout.as_deref() {
+						Some(stdout) if !stdout.is_empty() => {
+							writeln!(writer, "---- {} stdout ----", v.name)?;
+							writeln!(writer, "{}", stdout.trim_end())
+						}
+						_ => Ok(())
+					}
+				}
+			},
+			cargo::CargoMessage::Bench(v) => match v.mib_per_second {
+				Some(throughput) => writeln!(writer, "test {} ... bench: {} ns/iter (+/- {}) = {} MiB/s",
+					v.name, v.median, v.deviation, throughput),
+				None => writeln!(writer, "test {} ... bench: {} ns/iter (+/- {})", v.name, v.median, v.deviation)
+			},
+			cargo::CargoMessage::Build(v) => v.echo(writer),
+			cargo::CargoMessage::Running(_) => Ok(())
+		}
+	}
+}
+
+fn echo_suite_result(writer: &mut dyn io::Write, result: &str, v: &cargo::CargoTestReportSuiteOkOrFailed) -> io::Result<()> {
+	writeln!(writer, "\ntest result: {}. {} passed; {} failed; {} ignored; {} measured; {} filtered out; finished in {:.2}s",
+		result, v.passed, v.failed, v.ignored, v.measured, v.filtered_out, v.exec_time)
+}
+
+impl Echo for clippy::Message {
+	fn echo(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		match self {
+			clippy::Message::CompilerMessage(v) => write!(writer, "{}", v.message.rendered),
+			_ => Ok(())
+		}
+	}
+}
+
+impl Echo for audit::Report {
+	fn echo(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		let issues = self.vulnerabilities.list.iter()
+			.map(|issue| ("vulnerability", issue))
+			.chain(self.warnings.iter().flat_map(|(kind, issues)| issues.iter()
+				.map(move |issue| (kind.as_str(), issue))))
+			.map(|(kind, issue)| vec![
+				issue.advisory.as_ref().map_or_else(|| "-".to_string(), |v| v.id.clone()),
+				issue.package.as_ref().map_or_else(|| "-".to_string(), |v| v.name.clone()),
+				issue.package.as_ref().map_or_else(|| "-".to_string(), |v| v.version.clone()),
+				kind.to_string(),
+				issue.advisory.as_ref().map_or_else(String::new, |v| v.title.clone())
+			])
+			.collect::<Vec<_>>();
+		
+		writeln!(writer, "\ncargo audit: {} vulnerabilities, {} warnings in {} dependencies",
+			self.vulnerabilities.count, issues.len() - self.vulnerabilities.list.len(), self.lockfile.dependency_count)?;
+		echo_table(writer, &["ID", "Package", "Version", "Kind", "Title"], &issues)
+	}
+}
+
+impl Echo for geiger::Report {
+	fn echo(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		let packages = self.packages.iter()
+			.map(|package| {
+				let used   = &package.unsafety.used;
+				let unused = &package.unsafety.unused;
+				vec![
+					package.package.id.name.clone(),
+					package.package.id.version.clone(),
+					(used.functions.unsafe_ + used.exprs.unsafe_ + used.item_impls.unsafe_
+						+ used.item_traits.unsafe_ + used.methods.unsafe_).to_string(),
+					(unused.functions.unsafe_ + unused.exprs.unsafe_ + unused.item_impls.unsafe_
+						+ unused.item_traits.unsafe_ + unused.methods.unsafe_).to_string(),
+					if package.unsafety.forbids_unsafe { "yes" } else { "no" }.to_string()
+				]
+			})
+			.collect::<Vec<_>>();
+		
+		writeln!(writer, "\ncargo geiger: {} packages", packages.len())?;
+		echo_table(writer, &["Package", "Version", "Unsafe (used)", "Unsafe (unused)", "Forbids unsafe"], &packages)
+	}
+}
+
+/// Prints a table with left aligned columns.
+fn echo_table(writer: &mut dyn io::Write, header: &[&str], rows: &[Vec<String>]) -> io::Result<()> {
+	let widths = header.iter()
+		.enumerate()
+		.map(|(i, title)| rows.iter()
+			.map(|row| row[i].chars().count())
+			.chain(Some(title.len()))
+			.max()
+			.unwrap_or_default())
+		.collect::<Vec<_>>();
+	
+	let header = header.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+	
+	for row in Some(&header).into_iter().chain(rows) {
+		let line = row.iter()
+			.zip(&widths)
+			.map(|(cell, width)| format!("{:width$}", cell, width = width))
+			.collect::<Vec<_>>()
+			.join("  ");
+		writeln!(writer, "{}", line.trim_end())?;
+	}
+	
+	Ok(())
+}
@@ -0,0 +1,159 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Errors
+
+use {super::*, std::{fmt, path::PathBuf}};
+
+/// Maximum number of characters of the offending input that are kept in an error.
+const SNIPPET_LEN: usize = 200;
+
+/// The stage of the conversion at which an error occurred.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Stage {
+	Config,
+	Parse,
+	Convert,
+	Write
+}
+
+#[derive(Debug)]
+pub enum Error {
+	/// The configuration file could not be read or is invalid.
+	Config {
+		path:    PathBuf,
+		message: String
+	},
+	/// Reading the input or writing the report failed.
+	Io {
+		stage:  Stage,
+		source: io::Error
+	},
+	/// A message is not valid JSON or does not have the expected structure.
+	Parse {
+		line:    usize,
+		snippet: String,
+		source:  serde_json::Error
+	},
+	/// A message is well-formed, but does not fit into the messages before it.
+	Convert {
+		line:    Option<usize>,
+		snippet: Option<String>,
+		message: String
+	}
+}
+
+impl Error {
+	pub fn convert(message: impl Into<String>) -> Self {
+		Self::Convert { line: None, snippet: None, message: message.into() }
+	}
+	
+	pub fn stage(&self) -> Stage {
+		match self {
+			Self::Config { .. }    => Stage::Config,
+			Self::Io { stage, .. } => *stage,
+			Self::Parse { .. }     => Stage::Parse,
+			Self::Convert { .. }   => Stage::Convert
+		}
+	}
+	
+	/// Returns the input line the error occurred at, if known.
+	pub fn line(&self) -> Option<usize> {
+		match self {
+			Self::Config { .. }        => None,
+			Self::Io { .. }            => None,
+			Self::Parse { line, .. }   => Some(*line),
+			Self::Convert { line, .. } => *line
+		}
+	}
+	
+	/// Attaches the input line to a conversion error, if it does not already have one.
+	pub fn at(self, line: usize, input: &str) -> Self {
+		match self {
+			Self::Convert { line: None, message, .. } => Self::Convert {
+				line:    Some(line),
+				snippet: Some(snippet(input)),
+				message
+			},
+			v => v
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Config { path, message }             => write!(f, "invalid configuration file {}: {}", path.display(), message),
+			Self::Io { stage: Stage::Config, source }  => write!(f, "failed to read configuration: {}", source),
+			Self::Io { stage: Stage::Parse, source }   => write!(f, "failed to read input: {}", source),
+			Self::Io { stage: Stage::Convert, source } => write!(f, "failed to convert input: {}", source),
+			Self::Io { stage: Stage::Write, source }   => write!(f, "failed to write report: {}", source),
+			Self::Parse { line, snippet, source }      => write!(f, "line {}: failed to parse message: {}\n    {}", line, source, snippet),
+			Self::Convert { line, snippet, message }   => {
+				if let Some(line) = line {
+					write!(f, "line {}: ", line)?;
+				}
+				
+				write!(f, "failed to convert message: {}", message)?;
+				
+				if let Some(snippet) = snippet {
+					write!(f, "\n    {}", snippet)?;
+				}
+				
+				Ok(())
+			}
+		}
+	}
+}
+
+/// A line of the input that was skipped, because it is not a valid message.
+#[derive(Clone, Debug)]
+pub struct Warning {
+	pub line:    usize,
+	pub snippet: String,
+	pub message: String
+}
+
+impl fmt::Display for Warning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "line {}: skipped invalid message: {}\n    {}", self.line, self.message, self.snippet)
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Self::Config { .. }        => None,
+			Self::Io { source, .. }    => Some(source),
+			Self::Parse { source, .. } => Some(source),
+			Self::Convert { .. }       => None
+		}
+	}
+}
+
+/// Truncates the offending input to a length suitable for an error message.
+pub(crate) fn snippet(input: &str) -> String {
+	match input.char_indices().nth(SNIPPET_LEN) {
+		Some((i, _)) => format!("{}...", &input[..i]),
+		None         => input.to_string()
+	}
+}
@@ -23,7 +23,7 @@
 use super::*;
 
 #[derive(Clone, Debug, Default, Deserialize)]
-pub struct RustFmtReport(Vec<RustFmtReportFile>);
+pub struct RustFmtReport(pub Vec<RustFmtReportFile>);
 
 #[derive(Clone, Debug, Default, Deserialize)]
 pub struct RustFmtReportFile {
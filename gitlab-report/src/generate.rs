@@ -20,330 +20,1101 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+//! Conversions from cargo's output into GitLab reports.
+
 use super::*;
 
-pub fn test_to_junit(
-	reader: impl io::BufRead,
-	mut writer: impl io::Write
-) {
-	let mut suites = Vec::new();
-	
-	for line in reader.lines() {
-		let msg = match line.and_then(|line| serde_json::from_str(&line)
-			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
-		{
-			Ok(v) => v,
-			Err(e) => {
-				eprintln!("error: failed to parse message: {}", e);
-				std::process::exit(1);
-			}
-		};
+/// Converts libtest's JSON output, or the libtest compatible output of cargo-nextest, into a JUnit report.
+#[derive(Clone, Debug, Default)]
+pub struct TestToJunit {
+	config:      config::Config,
+	names:       SuiteNames,
+	environment: Option<SuiteEnvironment>,
+	suites:      Vec<junit::Testsuite>,
+	/// The suites that did not finish yet, in the order they were started.
+	open:        Vec<OpenSuite>,
+	/// Why the input ended prematurely, if it was interrupted.
+	interrupted: Option<String>
+}
+
+#[derive(Clone, Debug, Default)]
+struct OpenSuite {
+	/// The test binary of the suite, as nextest names it, or empty for libtest.
+	binary:   String,
+	/// The index of the suite.
+	index:    usize,
+	/// The tests that did not finish yet, by their name.
+	running:  HashMap<String, RunningTest>,
+	/// The tests that finished, by their name, to recognise retries.
+	finished: HashMap<String, usize>
+}
+
+#[derive(Clone, Debug)]
+struct RunningTest {
+	/// The index of the test in its suite.
+	index:     usize,
+	/// The time the test was started at, for the wall-clock fallback.
+	started:   f64,
+	/// Whether libtest reported the test to run for over 60 seconds.
+	timed_out: bool
+}
+
+impl TestToJunit {
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
+	}
+	
+	/// Sets the environment the tests ran in, which is detected from the own environment otherwise.
+	pub fn with_environment(mut self, environment: SuiteEnvironment) -> Self {
+		self.environment = Some(environment);
+		self
+	}
+	
+	fn find_suite(&self, binary: &str) -> Option<usize> {
+		find_suite(self.open.iter().map(|suite| suite.binary.as_str()), binary)
+	}
+	
+	/// Reports the tests of a suite that did not finish as errors.
+	fn crash_suite(&mut self, pos: usize) {
+		let open      = self.open.remove(pos);
+		let suite     = &mut self.suites[open.index];
+		let testcases = suite.testcases.get_or_insert_with(Vec::new);
+		
+		for (_, test) in open.running {
+			testcases[test.index].status = Some(match &self.interrupted {
+				Some(reason) => junit::TestsuiteTestcaseStatus::Error {
+					r#type:  "killed".to_string(),
+					message: reason.clone()
+				},
+				None => junit::TestsuiteTestcaseStatus::Error {
+					r#type:  "crash".to_string(),
+					message: match test.timed_out {
+						true  => "the test ran for over 60 seconds and did not finish, the test binary was killed or crashed",
+						false => "the test did not finish, the test binary was killed or crashed"
+					}.to_string()
+				}
+			});
+		}
+		
+		suite.failures = testcases.iter().filter(|v| matches!(v.status, Some(junit::TestsuiteTestcaseStatus::Failure { .. }))).count();
+		suite.errors   = testcases.iter().filter(|v| matches!(v.status, Some(junit::TestsuiteTestcaseStatus::Error { .. }))).count();
+		suite.skipped  = testcases.iter().filter(|v| matches!(v.status, Some(junit::TestsuiteTestcaseStatus::Skipped { .. }))).count();
+		suite.time     = testcases.iter().map(|v| v.time).sum();
+		set_property(&mut suite.properties.get_or_insert_with(Default::default).property, "error", match &self.interrupted {
+			Some(reason) => reason,
+			None         => "the test binary exited before the test suite finished, it was killed or crashed"
+		});
+	}
+}
+
+impl Converter for TestToJunit {
+	type Input  = cargo::CargoMessage;
+	type Output = junit::Report;
+	
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
+		self.names.push(&msg);
 		
 		match msg {
 			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Started(v)) => {
-				let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_nanos();
-				suites.push(junit::Testsuite {
-					id:         suites.len(),
-					name:       format!("cargo test #{}", suites.len()),
-					timestamp:  chrono::NaiveDateTime::from_timestamp(
-						(now / 1_000_000_000) as _, (now % 1_000_000_000) as _)
-						.format("%Y-%m-%dT%H:%M:%S").to_string(),
-					hostname:   "localhost".to_string(),
+				let binary = v.nextest.as_ref().map(cargo::NextestSuite::binary_id).unwrap_or_default();
+				
+				// a test binary that starts again, or another one of libtest, means the last one did not finish
+				if let Some(pos) = self.open.iter().position(|suite| suite.binary == binary) {
+					self.crash_suite(pos);
+				}
+				
+				let environment = self.environment.get_or_insert_with(|| SuiteEnvironment::detect(None));
+				self.open.push(OpenSuite { binary, index: self.suites.len(), ..Default::default() });
+				self.suites.push(junit::Testsuite {
+					id:         self.suites.len(),
+					name:       self.names.started(v.nextest.as_ref()),
+					timestamp:  environment.timestamp.clone()
+						.unwrap_or_else(|| chrono::Utc::now().format(TIMESTAMP_FORMAT).to_string()),
+					hostname:   environment.hostname.clone(),
 					tests:      v.test_count,
+					properties: Some(junit::TestsuiteProperties { property: environment.properties.clone() }),
 					testcases:  Some(Vec::new()),
 					..Default::default()
 				});
 			}
 			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Ok(v) | cargo::CargoTestReportSuite::Failed(v)) => {
-				let suite = suites.last_mut().unwrap();
+				let binary = v.nextest.as_ref().map(cargo::NextestSuite::binary_id).unwrap_or_default();
+				let open   = self.open.remove(self.find_suite(&binary).ok_or_else(no_suite)?);
+				let suite  = &mut self.suites[open.index];
 				suite.failures = v.failed;
 				suite.errors   = 0;
-				suite.skipped  = v.ignored + v.filtered_out;
+				// tests that are filtered out were never meant to run, so they are not skipped
+				suite.skipped  = v.ignored;
 				suite.time     = v.exec_time;
+				set_property(&mut suite.properties.get_or_insert_with(Default::default).property, "filtered_out", &v.filtered_out.to_string());
 			}
-			cargo::CargoMessage::Test(cargo::CargoTestReportTest { name, event }) => {
-				let now = std::time::UNIX_EPOCH.elapsed().unwrap().as_secs_f64();
-				let suite = suites.last_mut().unwrap();
-				let testcases = suite.testcases.as_mut().unwrap();
-				let (module, name) = name.rsplit_once("::").unwrap_or(("", &name));
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Unknown) => (),
+			cargo::CargoMessage::Test(cargo::CargoTestReportTest { name: full_name, event }) => {
+				let now = std::time::UNIX_EPOCH.elapsed().unwrap_or_default().as_secs_f64();
+				let (binary, full_name) = full_name.split_once('$')
+					.map_or_else(|| (String::new(), full_name.clone()), |(binary, name)| (binary.to_string(), name.to_string()));
+				let pos  = self.find_suite(&binary).ok_or_else(no_suite)?;
+				let open = &mut self.open[pos];
+				let suite = &mut self.suites[open.index];
 				
-				match event {
-					cargo::CargoTestReportTestEvent::Started => suite.testcases.as_mut().unwrap().push(junit::TestsuiteTestcase {
-						status:    None,
-						name:      name.to_string(),
-						classname: module.to_string(),
-						time:      now
-					}),
-					cargo::CargoTestReportTestEvent::Ignored => {
-						let testcase = testcases.iter_mut()
-							.find(|case| case.classname == module && case.name == name)
-							.unwrap();
-						
-						testcase.time = now - testcase.time;
-						testcase.status = Some(junit::TestsuiteTestcaseStatus::Skipped);
+				if let Some(name) = self.names.identify(&binary) {
+					open.binary = binary;
+					suite.name  = name;
+				}
+				let testcases = suite.testcases.get_or_insert_with(Vec::new);
+				let testcase = match cargo::Doctest::parse(&full_name) {
+					// the file of a doctest is relative to its package, which is not the workspace in general
+					Some(doctest) => junit::TestsuiteTestcase {
+						name:      full_name.clone(),
+						classname: doctest.item.clone().unwrap_or_else(|| doctest.file.clone()),
+						file:      Some(self.config.path(&match &self.names.package_dir {
+							Some(dir) => format!("{}/{}", dir, doctest.file),
+							None      => doctest.file
+						})),
+						line:      Some(doctest.line),
+						..Default::default()
 					},
-					event @ cargo::CargoTestReportTestEvent::Ok(_) | event @ cargo::CargoTestReportTestEvent::Failed(_) => {
-						let testcase = testcases.iter_mut()
-							.find(|case| case.classname == module && case.name == name)
-							.unwrap();
-						
-						testcase.time = now - testcase.time;
-						testcase.status = match event {
-							cargo::CargoTestReportTestEvent::Ok(_v) => None,
-							cargo::CargoTestReportTestEvent::Failed(v) => Some(junit::TestsuiteTestcaseStatus::Failure {
-								r#type:  "cargo test".to_string(),
-								system_output: v.stdout.unwrap_or_default()
-							}),
-							_ => unreachable!()
+					None => {
+						let (module, name) = full_name.rsplit_once("::").unwrap_or(("", &full_name));
+						junit::TestsuiteTestcase {
+							name:      name.to_string(),
+							classname: module.to_string(),
+							..Default::default()
+						}
+					}
+				};
+				
+				let test = match event {
+					cargo::CargoTestReportTestEvent::Started => {
+						let index = match open.finished.remove(&full_name) {
+							// a test that is started again after it finished is retried
+							Some(index) => {
+								retried(&mut testcases[index]);
+								index
+							}
+							None => {
+								testcases.push(testcase);
+								testcases.len() - 1
+							}
 						};
+						
+						open.running.insert(full_name, RunningTest { index, started: now, timed_out: false });
+						return Ok(());
 					}
-				}
+					cargo::CargoTestReportTestEvent::Timeout => {
+						if let Some(test) = open.running.get_mut(&full_name) {
+							test.timed_out = true;
+						}
+						
+						return Ok(());
+					}
+					cargo::CargoTestReportTestEvent::Unknown => return Ok(()),
+					_ => match (open.running.remove(&full_name), open.finished.remove(&full_name)) {
+						(Some(test), _) => test,
+						// a test that finishes again without being started again is retried as well
+						(None, Some(index)) => {
+							retried(&mut testcases[index]);
+							RunningTest { index, started: now, timed_out: false }
+						}
+						// tests that finish without being started, like ignored tests of older versions of libtest, are added now
+						(None, None) => {
+							testcases.push(testcase);
+							RunningTest { index: testcases.len() - 1, started: now, timed_out: false }
+						}
+					}
+				};
+				
+				open.finished.insert(full_name.clone(), test.index);
+				
+				let testcase = &mut testcases[test.index];
+				testcase.time = match &event {
+					cargo::CargoTestReportTestEvent::Ok(cargo::CargoTestReportTestOkOrFailed { exec_time: Some(v), .. })
+					| cargo::CargoTestReportTestEvent::Failed(cargo::CargoTestReportTestOkOrFailed { exec_time: Some(v), .. }) => *v,
+					// ignored tests do not run, so they do not take any time
+					cargo::CargoTestReportTestEvent::Ignored(_) => 0.0,
+					_ => {
+						set_property(&mut suite.properties.get_or_insert_with(Default::default).property, "time-source", "wall-clock");
+						now - test.started
+					}
+				};
+				testcase.status = match event {
+					cargo::CargoTestReportTestEvent::Started
+					| cargo::CargoTestReportTestEvent::Timeout
+					| cargo::CargoTestReportTestEvent::Unknown => unreachable!(),
+					cargo::CargoTestReportTestEvent::Ignored(v) => Some(junit::TestsuiteTestcaseStatus::Skipped { message: v.message }),
+					cargo::CargoTestReportTestEvent::Ok(v)     => {
+						// the test passed after it was retried, so it is flaky
+						testcase.flaky_failures = std::mem::take(&mut testcase.rerun_failures);
+						
+						// libtest only emits the output of passing tests with `--show-output`
+						if let Some(stdout) = v.stdout.filter(|v| !v.is_empty()) {
+							testcase.system_out.get_or_insert_with(Default::default).content = test_output(&self.config, &stdout);
+						}
+						
+						None
+					}
+					cargo::CargoTestReportTestEvent::Failed(v) => {
+						let panic = v.panic();
+						
+						// locations outside of the repository, like the standard library's, cannot be linked to,
+						// and doctests already point at their documentation
+						if let Some(panic) = panic.as_ref().filter(|v| std::path::Path::new(&v.file).is_relative() && testcase.file.is_none()) {
+							testcase.file = Some(self.config.path(&panic.file));
+							testcase.line = Some(panic.line);
+						}
+						
+						// files the test left behind, like new snapshots, are shown by GitLab along with the test
+						for path in self.config.attachments(&full_name)? {
+							testcase.system_out.get_or_insert_with(Default::default).attach(&path);
+						}
+						
+						Some(junit::TestsuiteTestcaseStatus::Failure {
+							r#type:        "cargo test".to_string(),
+							message:       match &panic {
+								Some(panic) => Some(test_output(&self.config, &panic.message)),
+								None        => None
+							},
+							system_output: test_output(&self.config, v.stdout.as_deref().unwrap_or_default())
+						})
+					}
+				};
 			}
-			cargo::CargoMessage::Bench(_) => ()
+			cargo::CargoMessage::Bench(_) | cargo::CargoMessage::Build(_) | cargo::CargoMessage::Running(_) => ()
 		}
+		
+		Ok(())
 	}
 	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m JUnit report");
+	fn interrupt(&mut self, reason: &str) {
+		self.interrupted = Some(reason.to_string());
+	}
 	
-	if let Err(e) = writeln!(&mut writer, "<?xml version=\"1.0\" encoding=\"utf-8\"?>") {
-		eprintln!("error: failed to generate report: {:?}", e);
-		std::process::exit(1);
-	} else if let Err(e) = quick_xml::se::to_writer(writer, &junit::Report(suites)) {
-		eprintln!("error: failed to generate report: {:?}", e);
-		std::process::exit(1);
+	fn finish(mut self) -> Result<Self::Output, Error> {
+		while !self.open.is_empty() {
+			self.crash_suite(0);
+		}
+		
+		Ok(junit::Report(self.suites))
+	}
+}
+
+/// Converts libtest's JSON output into OpenMetrics, one set of metrics per test suite, the duration of each
+/// test and the number of passed, failed and ignored tests and the distribution of their durations per crate.
+///
+/// The durations of tests are only known with libtest's `--report-time`. Tests that are retried by nextest
+/// are counted with the outcome and duration of their last attempt.
+#[derive(Clone, Debug, Default)]
+pub struct TestToOpenMetrics {
+	config:  config::Config,
+	names:   SuiteNames,
+	/// The nextest binary id, or empty for libtest, suite and crate of the running test binaries.
+	suites:  Vec<(String, String, String)>,
+	/// The outcome and duration of each test, by its crate, suite and name.
+	tests:   std::collections::BTreeMap<(String, String, String), (TestOutcome, Option<f64>)>,
+	metrics: open_metrics::Report
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum TestOutcome {
+	Passed,
+	Failed,
+	Ignored
+}
+
+/// The upper bounds of the buckets of the test duration histograms, in seconds.
+const TEST_DURATION_BUCKETS: &[f64] = &[0.001, 0.01, 0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0];
+
+impl TestToOpenMetrics {
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
+	}
+}
+
+impl TestToOpenMetrics {
+	fn find_suite(&self, binary: &str) -> Option<usize> {
+		find_suite(self.suites.iter().map(|(binary, ..)| binary.as_str()), binary)
 	}
 }
 
-pub fn test_to_open_metrics(
-	reader: impl io::BufRead,
-	mut writer: impl io::Write
-) {
-	let mut i = 0;
+impl Converter for TestToOpenMetrics {
+	type Input  = cargo::CargoMessage;
+	type Output = open_metrics::Report;
 	
-	for line in reader.lines() {
-		let msg = match line.and_then(|line| serde_json::from_str(&line)
-			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
-		{
-			Ok(v) => v,
-			Err(e) => {
-				eprintln!("error: failed to parse message: {}", e);
-				std::process::exit(1);
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
+		self.names.push(&msg);
+		
+		match msg {
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Started(v)) => {
+				let binary = v.nextest.as_ref().map(cargo::NextestSuite::binary_id).unwrap_or_default();
+				let suite  = self.names.started(v.nextest.as_ref());
+				let krate  = self.names.crate_name.clone().unwrap_or_else(|| "unknown".to_string());
+				self.suites.retain(|(v, ..)| *v != binary);
+				self.suites.push((binary, suite, krate));
 			}
-		};
+			cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Ok(v) | cargo::CargoTestReportSuite::Failed(v)) => {
+				let binary = v.nextest.as_ref().map(cargo::NextestSuite::binary_id).unwrap_or_default();
+				let suite  = self.find_suite(&binary)
+					.map(|pos| self.suites.remove(pos).1)
+					.or_else(|| v.nextest.as_ref().map(cargo::NextestSuite::suite_name))
+					.ok_or_else(no_suite)?;
+				let labels = Some(("suite".to_string(), suite)).into_iter()
+					.chain(self.config.labels())
+					.collect::<Vec<_>>();
+				
+				for (name, help, value) in [
+					("passed",       "The number of tests of the suite that passed.",                   v.passed as f64),
+					("failed",       "The number of tests of the suite that failed.",                   v.failed as f64),
+					("allowed_fail", "The number of tests of the suite that failed, but were allowed to.", v.allowed_fail as f64),
+					("ignored",      "The number of tests of the suite that were ignored.",             v.ignored as f64),
+					("measured",     "The number of benchmarks of the suite that were measured.",       v.measured as f64),
+					("filtered_out", "The number of tests of the suite that were filtered out.",        v.filtered_out as f64)
+				] {
+					self.metrics.family(name, open_metrics::MetricType::Gauge, help)
+						.push(labels.clone(), value);
+				}
+				
+				self.metrics.family("exec_time_seconds", open_metrics::MetricType::Gauge, "The time it took to run the suite.")
+					.with_unit("seconds")
+					.push(labels, v.exec_time);
+			}
+			cargo::CargoMessage::Test(cargo::CargoTestReportTest { name, event }) => {
+				let (binary, name) = name.split_once('$')
+					.map_or_else(|| (String::new(), name.clone()), |(binary, name)| (binary.to_string(), name.to_string()));
+				let pos = self.find_suite(&binary).ok_or_else(no_suite)?;
+				
+				if let Some(name) = self.names.identify(&binary) {
+					let krate = self.names.crate_name.clone().unwrap_or_else(|| "unknown".to_string());
+					self.suites[pos] = (binary, name, krate);
+				}
+				
+				let (_, suite, krate) = self.suites[pos].clone();
+				let outcome = match event {
+					cargo::CargoTestReportTestEvent::Ok(v)      => (TestOutcome::Passed, v.exec_time),
+					cargo::CargoTestReportTestEvent::Failed(v)  => (TestOutcome::Failed, v.exec_time),
+					cargo::CargoTestReportTestEvent::Ignored(_) => (TestOutcome::Ignored, None),
+					_ => return Ok(())
+				};
+				
+				self.tests.insert((krate, suite, name), outcome);
+			}
+			_ => ()
+		}
 		
-		if let cargo::CargoMessage::Suite(cargo::CargoTestReportSuite::Ok(v) | cargo::CargoTestReportSuite::Failed(v)) = msg {
-			if let Err(e) = write!(&mut writer,
-								   r#"passed{{suite={0}}}: {1}
-failed{{suite={0}}}: {2}
-allowed_fail{{suite={0}}}: {3}
-ignored{{suite={0}}}: {4}
-measured{{suite={0}}}: {5}
-filtered_out{{suite={0}}}: {6}
-exec_time{{suite={0}}}: {7}
-"#, format!("cargo test #{}", i), v.passed, v.failed, v.allowed_fail, v.ignored, v.measured, v.filtered_out, v.exec_time) {
-				eprintln!("error: failed to generate report: {:?}", e);
-				std::process::exit(1);
+		Ok(())
+	}
+	
+	fn finish(mut self) -> Result<Self::Output, Error> {
+		let labels = self.config.labels();
+		let mut crates = std::collections::BTreeMap::<&str, ([usize; 3], open_metrics::Histogram)>::new();
+		
+		for ((krate, suite, test), (outcome, duration)) in &self.tests {
+			let (counts, histogram) = crates.entry(krate.as_str())
+				.or_insert_with(|| ([0; 3], open_metrics::Histogram::new(TEST_DURATION_BUCKETS)));
+			counts[*outcome as usize] += 1;
+			
+			if let Some(duration) = duration {
+				histogram.observe(*duration);
+				self.metrics.family("test_duration_seconds", open_metrics::MetricType::Gauge, "The time it took to run the test.")
+					.with_unit("seconds")
+					.push(vec![
+						("crate".to_string(), krate.clone()),
+						("suite".to_string(), suite.clone()),
+						("test".to_string(),  test.clone())
+					].into_iter().chain(labels.iter().cloned()).collect(), *duration);
+			}
+		}
+		
+		for (krate, (counts, histogram)) in crates {
+			let labels = Some(("crate".to_string(), krate.to_string())).into_iter()
+				.chain(labels.iter().cloned())
+				.collect::<Vec<_>>();
+			
+			for (outcome, name, help) in [
+				(TestOutcome::Passed,  "crate_tests_passed",  "The number of tests of the crate that passed."),
+				(TestOutcome::Failed,  "crate_tests_failed",  "The number of tests of the crate that failed."),
+				(TestOutcome::Ignored, "crate_tests_ignored", "The number of tests of the crate that were ignored.")
+			] {
+				self.metrics.family(name, open_metrics::MetricType::Counter, help)
+					.push(labels.clone(), counts[outcome as usize] as f64);
 			}
 			
-			i += 1;
+			self.metrics.family("crate_test_duration_seconds", open_metrics::MetricType::Histogram,
+				"The distribution of the time it took to run the tests of the crate.")
+				.with_unit("seconds")
+				.metrics.push(open_metrics::Metric { labels, value: open_metrics::Value::Histogram(histogram) });
 		}
+		
+		Ok(self.metrics)
+	}
+}
+
+/// Converts clippy's diagnostics into a Code Climate report.
+#[derive(Clone, Debug, Default)]
+pub struct ClippyToCodeQuality {
+	config: config::Config,
+	issues: Vec<code_climate::CodeQualityReportIssue>
+}
+
+impl ClippyToCodeQuality {
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
 	}
-	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m OpenMetrics report");
 }
 
-pub fn clippy_to_code_quality(
-	reader: impl io::BufRead,
-	mut writer: impl io::Write
-) {
-	let mut issues = Vec::new();
+impl Converter for ClippyToCodeQuality {
+	type Input  = clippy::Message;
+	type Output = code_climate::CodeQualityReport;
 	
-	for line in reader.lines() {
-		let msg = match line.and_then(|line| serde_json::from_str(&line)
-			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
-		{
-			Ok(v) => v,
-			Err(e) => {
-				eprintln!("error: failed to parse message: {}", e);
-				std::process::exit(1);
-			}
-		};
-		
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
 		let msg = match msg {
 			clippy::Message::CompilerMessage(v) if !v.message.spans.is_empty() => v,
-			_ => continue
+			_ => return Ok(())
+		};
+		
+		let check_name = msg.message.code.as_ref()
+			.map_or_else(|| "unknown".to_string(), |v| v.code.clone());
+		
+		if self.config.clippy.allow.contains(&check_name) {
+			return Ok(());
+		}
+		
+		let config = &self.config;
+		let location = |span: &clippy::CompilerMessageMessageSpan| {
+			let mut location: code_climate::CodeQualityReportLocation = span.clone().into();
+			location.path = config.path(&location.path);
+			location
 		};
 		
-		issues.push(code_climate::CodeQualityReportIssue {
+		self.issues.push(code_climate::CodeQualityReportIssue {
 			r#type:             code_climate::CODE_QUALITY_REPORT_TYPE,
-			check_name:         msg.message.code.as_ref()
-				.map_or_else(|| "unknown".to_string(), |v| v.code.clone()),
+			check_name,
 			description:        msg.message.message.clone(),
 			content:            Some(format!("```{}```", msg.message.rendered)),
 			categories:         vec![code_climate::CodeQualityReportIssueCategory::Style],
-			location:           msg.message.spans[0].clone().into(),
+			location:           location(&msg.message.spans[0]),
 			other_locations:    (msg.message.spans.len() > 1).then(|| msg.message.spans[1..]
 				.iter()
-				.map(|span| span.clone().into())
+				.map(location)
 				.collect()),
 			remediation_points: None,
-			severity:           Some(match &*msg.message.level {
-				"error"   => code_climate::CodeQualityReportIssueSeverity::Major,
-				"warning" => code_climate::CodeQualityReportIssueSeverity::Minor,
-				_         => code_climate::CodeQualityReportIssueSeverity::Info
+			severity:           Some(match (config.clippy.severity.get(&msg.message.level), &*msg.message.level) {
+				(Some(severity), _) => severity.clone(),
+				(None, "error")     => code_climate::CodeQualityReportIssueSeverity::Major,
+				(None, "warning")   => code_climate::CodeQualityReportIssueSeverity::Minor,
+				(None, _)           => code_climate::CodeQualityReportIssueSeverity::Info
 			}),
 			fingerprint:        Some(format!("{:x}", xxhash_rust::xxh3::xxh3_128(msg.message.message.as_bytes())))
 		});
+		
+		Ok(())
 	}
 	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m code quality report");
-	
-	if let Err(e) = serde_json::to_writer(&mut writer, &issues) {
-		eprintln!("error: failed to generate report: {}", e);
-		std::process::exit(1);
+	fn finish(self) -> Result<Self::Output, Error> {
+		Ok(code_climate::CodeQualityReport(self.issues))
 	}
 }
 
-pub fn clippy_to_open_metrics(
-	reader: impl io::BufRead,
-	mut writer: impl io::Write
-) {
-	let mut metrics = HashMap::new();
+/// Converts clippy's diagnostics into OpenMetrics, counting them by level, lint, crate and the top-level directory
+/// of their file, with the total of each level and the number of distinct lints.
+///
+/// The summaries of the diagnostics, like `aborting due to 2 previous errors`, have neither a lint nor a location
+/// and are not counted. Diagnostics that are emitted for several targets of a package, like the library and its
+/// tests with `--all-targets`, are counted once.
+#[derive(Clone, Debug, Default)]
+pub struct ClippyToOpenMetrics {
+	config:  config::Config,
+	/// The number of diagnostics by their level, lint, crate and directory.
+	metrics: HashMap<(String, String, String, String), usize>,
+	/// The hashes of the package and the rendered message of the diagnostics that were counted.
+	seen:    std::collections::HashSet<u128>
+}
+
+impl ClippyToOpenMetrics {
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
+	}
+}
+
+impl Converter for ClippyToOpenMetrics {
+	type Input  = clippy::Message;
+	type Output = open_metrics::Report;
 	
-	for line in reader.lines() {
-		let msg = match line.and_then(|line| serde_json::from_str(&line)
-			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
-		{
-			Ok(v) => v,
-			Err(e) => {
-				eprintln!("error: failed to parse message: {}", e);
-				std::process::exit(1);
-			}
-		};
-		
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
 		let msg = match msg {
-			clippy::Message::CompilerMessage(v) if v.message.spans.is_empty() => v,
-			_ => continue
+			clippy::Message::CompilerMessage(v) if !v.message.spans.is_empty() || v.message.code.is_some() => v,
+			_ => return Ok(())
 		};
 		
-		*metrics.entry((msg.message.level, msg.message.code.as_ref()
-			.map_or_else(|| "unknown".to_string(), |v| v.code.clone())))
-			.or_insert(0) += 1;
+		let code = msg.message.code.as_ref()
+			.map_or_else(|| "unknown".to_string(), |v| v.code.clone());
+		
+		let hash = xxhash_rust::xxh3::xxh3_128(format!("{}\0{}",
+			msg.package_id.as_deref().unwrap_or_default(), msg.message.rendered).as_bytes());
+		
+		if self.config.clippy.allow.contains(&code) || !self.seen.insert(hash) {
+			return Ok(());
+		}
+		
+		let krate = msg.package_id.as_deref().map_or("unknown", clippy::package_name).to_string();
+		let dir   = msg.message.spans.iter()
+			.find(|span| span.is_primary)
+			.or_else(|| msg.message.spans.first())
+			.map_or_else(String::new, |span| match span.file_name.split_once('/') {
+				Some((dir, _)) => dir.to_string(),
+				// files at the root of the package, like `build.rs`
+				None           => ".".to_string()
+			});
+		
+		*self.metrics.entry((msg.message.level, code, krate, dir)).or_insert(0) += 1;
+		Ok(())
 	}
 	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m OpenMetrics report");
-	
-	for ((level, code), value) in metrics {
-		if let Err(e) = writeln!(&mut writer, "{}{{code={}}}: {}", level, code, value) {
-			eprintln!("error: failed to generate report: {}", e);
-			std::process::exit(1);
+	fn finish(self) -> Result<Self::Output, Error> {
+		let labels  = self.config.labels();
+		let mut metrics = self.metrics.into_iter().collect::<Vec<_>>();
+		let mut levels  = std::collections::BTreeMap::new();
+		let mut codes   = std::collections::BTreeSet::new();
+		let mut report  = open_metrics::Report::default();
+		metrics.sort();
+		
+		// errors and warnings are always reported, so that a trend starts at zero rather than being missing
+		levels.insert("error".to_string(), 0);
+		levels.insert("warning".to_string(), 0);
+		
+		for ((level, code, krate, dir), value) in metrics {
+			*levels.entry(level.clone()).or_insert(0) += value;
+			
+			if code != "unknown" {
+				codes.insert(code.clone());
+			}
+			
+			report.family("clippy_diagnostics", open_metrics::MetricType::Gauge,
+				"The number of diagnostics by their level, lint, crate and the top-level directory of their file.")
+				.push(vec![
+					("level".to_string(), level),
+					("code".to_string(),  code),
+					("crate".to_string(), krate),
+					("dir".to_string(),   dir)
+				].into_iter().chain(labels.iter().cloned()).collect(), value as f64);
 		}
+		
+		for (level, value) in levels {
+			report.family("clippy_diagnostics_by_level", open_metrics::MetricType::Gauge, "The number of diagnostics of each level.")
+				.push(Some(("level".to_string(), level)).into_iter().chain(labels.iter().cloned()).collect(), value as f64);
+		}
+		
+		report.family("clippy_lints", open_metrics::MetricType::Gauge, "The number of distinct lints with diagnostics.")
+			.push(labels, codes.len() as f64);
+		Ok(report)
 	}
 }
 
-pub fn bench_to_open_metrics(
-	reader: impl io::BufRead,
-	mut writer: impl io::Write
-) {
-	for line in reader.lines() {
-		let msg = match line.and_then(|line| serde_json::from_str(&line)
-			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)))
-		{
-			Ok(v) => v,
-			Err(e) => {
-				eprintln!("error: failed to parse message: {}", e);
-				std::process::exit(1);
-			}
-		};
-		
+/// Converts libtest's JSON benchmark output into OpenMetrics, the median and deviation of the time of an
+/// iteration and the throughput of each benchmark, labelled with its name.
+#[derive(Clone, Debug, Default)]
+pub struct BenchToOpenMetrics {
+	config:  config::Config,
+	metrics: open_metrics::Report
+}
+
+impl BenchToOpenMetrics {
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
+	}
+}
+
+impl Converter for BenchToOpenMetrics {
+	type Input  = cargo::CargoMessage;
+	type Output = open_metrics::Report;
+	
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
 		if let cargo::CargoMessage::Bench(v) = msg {
-			if let Err(e) = writeln!(&mut writer, "{}: {}", v.name, v.median) {
-				eprintln!("error: failed to generate report: {}", e);
-				std::process::exit(1);
+			let labels = Some(("bench".to_string(), v.name)).into_iter()
+				.chain(self.config.labels())
+				.collect::<Vec<_>>();
+			
+			for (name, unit, help, value) in [
+				("median_ns",                 "ns",             "The median time of an iteration of the benchmark.",           Some(v.median)),
+				("deviation_ns",              "ns",             "The deviation of the time of an iteration of the benchmark.", Some(v.deviation)),
+				("throughput_mib_per_second", "mib_per_second", "The throughput of the benchmark.",                            v.mib_per_second)
+			] {
+				if let Some(value) = value {
+					self.metrics.family(name, open_metrics::MetricType::Gauge, help)
+						.with_unit(unit)
+						.push(labels.clone(), value);
+				}
 			}
 		}
+		
+		Ok(())
 	}
 	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m OpenMetrics report");
+	fn finish(self) -> Result<Self::Output, Error> {
+		Ok(self.metrics)
+	}
 }
 
-pub fn audit_to_gitlab_security_report(
+/// Converts a `cargo audit` report into a GitLab security report.
+#[derive(Clone, Debug)]
+pub struct AuditToGitlabSecurityReport {
+	config: config::Config,
 	format: gitlab_security_report::ScanType,
-	reader: impl io::BufRead,
-	writer: impl io::Write
-) {
-	let mut report = gitlab_security_report::Report { version: "2.0".to_string(), ..Default::default() };
-	let scanner    = gitlab_security_report::VulnerabilityScanner { id: "cargo_audit".to_string(), name: "Cargo Audit".to_string() };
-	let audit      = match serde_json::from_reader::<_, audit::Report>(reader) {
-		Ok(v) => v,
-		Err(e) => {
-			eprintln!("error: failed to parse report: {}", e);
-			std::process::exit(1);
+	report: gitlab_security_report::Report
+}
+
+impl AuditToGitlabSecurityReport {
+	pub fn new(format: gitlab_security_report::ScanType) -> Self {
+		Self {
+			config: config::Config::default(),
+			format,
+			report: gitlab_security_report::Report { version: "2.0".to_string(), ..Default::default() }
 		}
-	};
+	}
 	
-	eprintln!("  \x1b[32;1mGenerating\x1b[0m SAST report");
+	pub fn with_config(mut self, config: config::Config) -> Self {
+		self.config = config;
+		self
+	}
+}
+
+impl Converter for AuditToGitlabSecurityReport {
+	type Input  = audit::Report;
+	type Output = gitlab_security_report::Report;
 	
-	for vulnerability in audit.vulnerabilities.list {
-		report.vulnerabilities.push(gitlab_security_report::Vulnerability {
-			scanner: scanner.clone(),
-			..audit_issue_to_gitlab_vuln(vulnerability, format)
-		});
+	fn push(&mut self, audit: Self::Input) -> Result<(), Error> {
+		let scanner = gitlab_security_report::VulnerabilityScanner { id: "cargo_audit".to_string(), name: "Cargo Audit".to_string() };
+		
+		let config = &self.config.audit;
+		let issues = audit.vulnerabilities.list.into_iter()
+			.chain(audit.warnings.into_values().flatten())
+			.filter(|issue| !issue.advisory.as_ref()
+				.is_some_and(|advisory| config.ignore.contains(&advisory.id)));
+		
+		for issue in issues {
+			let severity = config.severity
+				.get(issue.kind.as_deref().unwrap_or("vulnerability"))
+				.cloned();
+			let vulnerability = audit_issue_to_gitlab_vuln(issue, self.format)?;
+			
+			self.report.vulnerabilities.push(gitlab_security_report::Vulnerability {
+				scanner:  scanner.clone(),
+				severity: severity.or(vulnerability.severity),
+				..vulnerability
+			});
+		}
+		
+		Ok(())
+	}
+	
+	fn finish(self) -> Result<Self::Output, Error> {
+		Ok(self.report)
+	}
+}
+
+/// Converts a `cargo geiger` report into a GitLab security report.
+#[derive(Clone, Debug)]
+pub struct GeigerToGitlabSecurityReport {
+	format: gitlab_security_report::ScanType,
+	report: gitlab_security_report::Report
+}
+
+impl GeigerToGitlabSecurityReport {
+	pub fn new(format: gitlab_security_report::ScanType) -> Self {
+		Self {
+			format,
+			report: gitlab_security_report::Report { version: "2.0".to_string(), ..Default::default() }
+		}
 	}
+}
+
+impl Converter for GeigerToGitlabSecurityReport {
+	type Input  = geiger::Report;
+	type Output = gitlab_security_report::Report;
 	
-	for (_, warnings) in audit.warnings {
-		for warning in warnings {
-			report.vulnerabilities.push(gitlab_security_report::Vulnerability {
+	fn push(&mut self, geiger: Self::Input) -> Result<(), Error> {
+		let scanner = gitlab_security_report::VulnerabilityScanner { id: "cargo_geiger".to_string(), name: "Cargo Geiger".to_string() };
+		
+		for package in geiger.packages {
+			let unsafe_ = package.unsafety.used.functions.unsafe_ + package.unsafety.unused.functions.unsafe_
+				+ package.unsafety.used.exprs.unsafe_ + package.unsafety.unused.exprs.unsafe_
+				+ package.unsafety.used.item_impls.unsafe_ + package.unsafety.unused.item_impls.unsafe_
+				+ package.unsafety.used.item_traits.unsafe_ + package.unsafety.unused.item_traits.unsafe_
+				+ package.unsafety.used.methods.unsafe_ + package.unsafety.unused.methods.unsafe_;
+			
+			if package.unsafety.forbids_unsafe && unsafe_ == 0 {
+				continue;
+			}
+			
+			self.report.vulnerabilities.push(gitlab_security_report::Vulnerability {
 				scanner: scanner.clone(),
-				..audit_issue_to_gitlab_vuln(warning, format)
+				..geiger_package_to_gitlab_vuln(package, self.format)?
 			});
 		}
+		
+		Ok(())
 	}
 	
-	if let Err(e) = serde_json::to_writer(writer, &report) {
-		eprintln!("error: failed to generate report: {}", e);
+	fn finish(self) -> Result<Self::Output, Error> {
+		Ok(self.report)
 	}
 }
 
-pub fn geiger_to_gitlab_security_report(
-	format: gitlab_security_report::ScanType,
-	reader: impl io::BufRead,
-	writer: impl io::Write
-) {
-	let mut report = gitlab_security_report::Report { version: "2.0".to_string(), ..Default::default() };
-	let scanner    = gitlab_security_report::VulnerabilityScanner { id: "cargo_geiger".to_string(), name: "Cargo Geiger".to_string() };
-	let geiger     = match serde_json::from_reader::<_, geiger::Report>(reader) {
-		Ok(v) => v,
-		Err(e) => {
-			eprintln!("error: failed to parse report: {}", e);
-			std::process::exit(1);
+/// Feeds each message to several converters, so that multiple reports are generated from a single input.
+pub struct FanOut<I>(pub Vec<Box<dyn DynConverter<I>>>);
+
+impl<I> Default for FanOut<I> {
+	fn default() -> Self {
+		Self(Vec::new())
+	}
+}
+
+impl<I: Clone> Converter for FanOut<I> {
+	type Input  = I;
+	type Output = Vec<Box<dyn AnyReport>>;
+	
+	fn push(&mut self, msg: Self::Input) -> Result<(), Error> {
+		for converter in &mut self.0 {
+			converter.push_dyn(msg.clone())?;
 		}
-	};
+		
+		Ok(())
+	}
 	
-	for package in geiger.packages {
-		let unsafe_ = package.unsafety.used.functions.unsafe_ + package.unsafety.unused.functions.unsafe_
-			+ package.unsafety.used.exprs.unsafe_ + package.unsafety.unused.exprs.unsafe_
-			+ package.unsafety.used.item_impls.unsafe_ + package.unsafety.unused.item_impls.unsafe_
-			+ package.unsafety.used.item_traits.unsafe_ + package.unsafety.unused.item_traits.unsafe_
-			+ package.unsafety.used.methods.unsafe_ + package.unsafety.unused.methods.unsafe_;
+	fn interrupt(&mut self, reason: &str) {
+		for converter in &mut self.0 {
+			converter.interrupt_dyn(reason);
+		}
+	}
+	
+	fn finish(self) -> Result<Self::Output, Error> {
+		self.0.into_iter()
+			.map(|converter| converter.finish_dyn())
+			.collect()
+	}
+}
+
+impl<I> FanOut<I> {
+	/// Returns the reports of the messages so far, e.g. to write a checkpoint that survives a killed process.
+	pub fn snapshot(&self) -> Result<Vec<Box<dyn AnyReport>>, Error> {
+		self.0.iter()
+			.map(|converter| converter.snapshot_dyn())
+			.collect()
+	}
+	
+	/// Writes the reports of the messages so far to their paths, which are replaced by the final reports later,
+	/// returns the paths that could not be written. Each report is written to a temporary file first, so that a
+	/// report is never left half-written.
+	pub fn write_checkpoint<'a>(&self, paths: impl IntoIterator<Item = Option<&'a str>>) -> Vec<(&'a str, io::Error)> {
+		// a checkpoint is only a fallback, failing to write it must not fail the report
+		let reports = match self.snapshot() {
+			Ok(reports) => reports,
+			Err(_)      => return Vec::new()
+		};
 		
-		if package.unsafety.forbids_unsafe && unsafe_ == 0 {
-			continue;
+		reports.iter()
+			.zip(paths)
+			.filter_map(|(report, path)| {
+				let path = path?;
+				let tmp  = format!("{}.tmp", path);
+				
+				std::fs::File::create(&tmp)
+					.and_then(|file| {
+						let mut writer = io::BufWriter::new(file);
+						report.write_report(&mut writer)?;
+						io::Write::flush(&mut writer)
+					})
+					.and_then(|_| std::fs::rename(&tmp, path))
+					.err()
+					.map(|e| (path, e))
+			})
+			.collect()
+	}
+}
+
+/// Names test suites after the test binaries cargo runs, like `my-crate::lib (unittests)`,
+/// `my-crate::tests/integration.rs` or `my-crate (doctests)`.
+///
+/// The binaries are announced by cargo's `Running ...` lines on STDERR, the packages they belong to by the
+/// artifact messages of `--message-format=json`. Without the latter, the name of the target is used instead
+/// of the name of the package, without the former, the suites are numbered.
+#[derive(Clone, Debug, Default)]
+struct SuiteNames {
+	/// The artifacts by the file name of their executable.
+	executables: HashMap<String, clippy::CompilerArtifact>,
+	/// The artifacts by the name of their library.
+	libraries:   HashMap<String, clippy::CompilerArtifact>,
+	next:        Option<cargo::CargoTestBinary>,
+	suites:      usize,
+	/// The directory of the package of the last started suite, relative to the current directory, if known.
+	package_dir: Option<String>,
+	/// The name of the crate of the last started suite, if known.
+	crate_name:  Option<String>,
+	/// Whether the last started suite is only named by its number.
+	unnamed:     bool,
+	/// The executables of the suites by their name, to tell apart binaries that would be named the same.
+	named:       HashMap<String, Option<String>>
+}
+
+impl SuiteNames {
+	fn push(&mut self, msg: &cargo::CargoMessage) {
+		match msg {
+			cargo::CargoMessage::Build(clippy::Message::CompilerArtifact(v)) => {
+				if let Some(file_name) = v.executable.as_deref().and_then(file_name) {
+					self.executables.insert(file_name.to_string(), v.clone());
+				}
+				
+				if v.target.kind.iter().any(|kind| kind.ends_with("lib") || kind == "proc-macro") {
+					self.libraries.insert(v.target.name.clone(), v.clone());
+				}
+			}
+			// `--verbose` prints the command line of a binary after the line that names its source, which is kept
+			cargo::CargoMessage::Running(cargo::CargoTestBinary::Target { src_path: None, executable })
+				if self.next.as_ref().and_then(cargo::CargoTestBinary::executable).and_then(file_name) == file_name(executable) => (),
+			cargo::CargoMessage::Running(v) => self.next = Some(v.clone()),
+			_ => ()
 		}
+	}
+	
+	/// Returns the name of the suite that was just started.
+	fn started(&mut self, nextest: Option<&cargo::NextestSuite>) -> String {
+		let binary = self.next.take().map(|binary| self.resolve(binary));
+		let name   = match (&binary, nextest) {
+			(_, Some(nextest)) => nextest.suite_name(),
+			(Some(binary), _)  => self.name(binary),
+			(None, None)       => format!("cargo test #{}", self.suites)
+		};
+		let executable = binary.as_ref().and_then(cargo::CargoTestBinary::executable).map(str::to_string);
+		let name       = self.unique(name, executable);
 		
-		report.vulnerabilities.push(gitlab_security_report::Vulnerability {
-			scanner: scanner.clone(),
-			..geiger_package_to_gitlab_vuln(package, format)
-		});
+		self.unnamed = binary.is_none() && nextest.is_none();
+		
+		self.crate_name = match (&binary, nextest) {
+			(_, Some(nextest)) => Some(nextest.crate_name.clone()),
+			(Some(binary), _)  => self.artifact(binary)
+				.map(|artifact| artifact.package_name().to_string())
+				.or_else(|| binary.target_name().map(str::to_string)),
+			(None, None)       => None
+		};
+		self.package_dir = binary.as_ref()
+			.and_then(|binary| self.artifact(binary))
+			.and_then(|artifact| artifact.manifest_path.as_deref())
+			.and_then(|path| Some(std::path::Path::new(path).parent()?.strip_prefix(std::env::current_dir().ok()?).ok()?.to_str()?.to_string()))
+			.filter(|dir| !dir.is_empty());
+		
+		self.suites += 1;
+		name
+	}
+	
+	/// Names the last suite after the nextest binary id its tests are prefixed with, if nothing else named it, as
+	/// nextest's `libtest-json` format only tells it in the names of the tests.
+	fn identify(&mut self, binary_id: &str) -> Option<String> {
+		if !self.unnamed || binary_id.is_empty() {
+			return None;
+		}
+		
+		let nextest = cargo::NextestSuite::from_binary_id(binary_id);
+		self.unnamed    = false;
+		self.crate_name = Some(nextest.crate_name.clone());
+		Some(nextest.suite_name())
 	}
 	
-	if let Err(e) = serde_json::to_writer(writer, &report) {
-		eprintln!("error: failed to generate report: {}", e);
+	/// Appends the hash of the executable to the name of a suite that another binary was already named, e.g. for
+	/// the unittests of a library and a binary of the same name, which `--verbose` only names by their executable.
+	fn unique(&mut self, name: String, executable: Option<String>) -> String {
+		let name = match self.named.get(&name) {
+			Some(other) if *other != executable => {
+				let hash = executable.as_deref()
+					.and_then(|v| std::path::Path::new(v).file_stem()?.to_str()?.rsplit_once('-'))
+					.map(|(_, hash)| hash);
+				
+				match hash {
+					Some(hash) => format!("{} ({})", name, hash),
+					None       => format!("{} #{}", name, self.suites)
+				}
+			}
+			_ => name
+		};
+		
+		self.named.insert(name.clone(), executable);
+		name
+	}
+	
+	/// Adds the source of a binary that `--verbose` only names by its executable, from its artifact.
+	fn resolve(&self, binary: cargo::CargoTestBinary) -> cargo::CargoTestBinary {
+		let artifact = match (&binary, self.artifact(&binary)) {
+			(cargo::CargoTestBinary::Target { src_path: None, .. }, Some(artifact)) => artifact,
+			_ => return binary
+		};
+		let src_path = artifact.manifest_path.as_deref()
+			.and_then(|path| std::path::Path::new(path).parent())
+			.and_then(|dir| std::path::Path::new(&artifact.target.src_path).strip_prefix(dir).ok())
+			.and_then(|path| path.to_str());
+		
+		match (binary, src_path) {
+			(cargo::CargoTestBinary::Target { executable, .. }, Some(src_path))
+				if artifact.target.kind.iter().any(|kind| kind.ends_with("lib") || kind == "proc-macro" || kind == "bin") =>
+				cargo::CargoTestBinary::Unittests { src_path: src_path.to_string(), executable },
+			(cargo::CargoTestBinary::Target { executable, .. }, Some(src_path)) =>
+				cargo::CargoTestBinary::Target { src_path: Some(src_path.to_string()), executable },
+			(binary, _) => binary
+		}
+	}
+	
+	fn artifact(&self, binary: &cargo::CargoTestBinary) -> Option<&clippy::CompilerArtifact> {
+		match binary {
+			cargo::CargoTestBinary::Unittests { executable, .. } | cargo::CargoTestBinary::Target { executable, .. } =>
+				self.executables.get(file_name(executable)?),
+			cargo::CargoTestBinary::Doctests { crate_name } => self.libraries.get(crate_name)
+		}
+	}
+	
+	fn name(&self, binary: &cargo::CargoTestBinary) -> String {
+		let target   = binary.target_name().unwrap_or_default();
+		let artifact = self.artifact(binary);
+		let package  = artifact.map(clippy::CompilerArtifact::package_name);
+		
+		match binary {
+			cargo::CargoTestBinary::Unittests { src_path, .. } => {
+				let lib = artifact.map_or(src_path == "src/lib.rs", |v| !v.target.kind.iter().any(|kind| kind == "bin"));
+				format!("{}::{} (unittests)", package.unwrap_or(target), if lib { "lib" } else { src_path })
+			}
+			cargo::CargoTestBinary::Target { src_path, .. } => match (package, src_path) {
+				(Some(package), Some(src_path)) => format!("{}::{}", package, src_path),
+				(None, Some(src_path))          => src_path.clone(),
+				(Some(package), None)           => format!("{}::{}", package, target),
+				(None, None)                    => target.to_string()
+			},
+			cargo::CargoTestBinary::Doctests { crate_name } =>
+				format!("{} (doctests)", package.unwrap_or(crate_name))
+		}
+	}
+}
+
+/// The format of the timestamps of JUnit reports, ISO 8601 without a time zone.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// The CI variables that are added as properties to every suite, with the name of their property.
+const CI_PROPERTIES: &[(&str, &str)] = &[
+	("commit",   "CI_COMMIT_SHA"),
+	("pipeline", "CI_PIPELINE_ID"),
+	("job",      "CI_JOB_NAME"),
+	("channel",  "CHANNEL"),
+	("profile",  "PROFILE"),
+	("target",   "TARGET")
+];
+
+/// The environment the tests ran in, so that a report of a matrix job tells which job it originates from.
+///
+/// The timestamp of the suites is the start of the job, `SOURCE_DATE_EPOCH` for reproducible reports or
+/// `CI_JOB_STARTED_AT`, or the time each suite started at otherwise. The properties are taken from the
+/// [`CI_PROPERTIES`] that are set and the versions of `rustc` and `cargo`, of the rustup toolchain the tests
+/// were run with, e.g. `nightly` for `cargo +nightly test`, or the default one.
+#[derive(Clone, Debug)]
+pub struct SuiteEnvironment {
+	pub hostname:   String,
+	pub timestamp:  Option<String>,
+	pub properties: Vec<junit::TestsuiteProperty>
+}
+
+impl SuiteEnvironment {
+	pub fn detect(toolchain: Option<&str>) -> Self {
+		let var = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+		let timestamp = var("SOURCE_DATE_EPOCH")
+			.and_then(|v| chrono::DateTime::from_timestamp(v.trim().parse().ok()?, 0))
+			.or_else(|| var("CI_JOB_STARTED_AT")
+				.and_then(|v| chrono::DateTime::parse_from_rfc3339(&v).ok())
+				.map(|v| v.with_timezone(&chrono::Utc)))
+			.map(|v| v.format(TIMESTAMP_FORMAT).to_string());
+		let mut properties = Vec::new();
+		
+		for (name, key) in CI_PROPERTIES {
+			if let Some(value) = var(key) {
+				set_property(&mut properties, name, &value);
+			}
+		}
+		
+		// the same variables cargo uses to find the tools
+		for (name, key) in [("rustc", "RUSTC"), ("cargo", "CARGO")] {
+			if let Some(version) = version(&var(key).unwrap_or_else(|| name.to_string()), toolchain) {
+				set_property(&mut properties, name, &version);
+			}
+		}
+		
+		Self {
+			hostname: gethostname::gethostname().into_string().ok()
+				.filter(|v| !v.is_empty())
+				.unwrap_or_else(|| "localhost".to_string()),
+			timestamp,
+			properties
+		}
 	}
 }
 
-fn audit_issue_to_gitlab_vuln(issue: audit::Issue, ty: gitlab_security_report::ScanType) -> gitlab_security_report::Vulnerability {
-	gitlab_security_report::Vulnerability {
+/// Prepares the output of a test for a report, without colors and truncated to the configured length.
+fn test_output(config: &config::Config, output: &str) -> String {
+	let (head, tail) = config.output_limits();
+	junit::truncate(&cargo::strip_ansi(output), head, tail)
+}
+
+/// Returns the output of `<program> --version`, e.g. `rustc 1.70.0 (90c541806 2023-05-31)`.
+fn version(program: &str, toolchain: Option<&str>) -> Option<String> {
+	let mut command = std::process::Command::new(program);
+	command.arg("--version");
+	
+	// the rustup proxies select the toolchain of `+<toolchain>` by this variable as well
+	if let Some(toolchain) = toolchain {
+		command.env("RUSTUP_TOOLCHAIN", toolchain);
+	}
+	
+	let output = command.output().ok()?;
+	
+	match output.status.success() {
+		true  => Some(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+		false => None
+	}
+}
+
+fn file_name(path: &str) -> Option<&str> {
+	std::path::Path::new(path).file_name()?.to_str()
+}
+
+fn set_property(properties: &mut Vec<junit::TestsuiteProperty>, name: &str, value: &str) {
+	match properties.iter_mut().find(|v| v.name == name) {
+		Some(property) => property.value = value.to_string(),
+		None           => properties.push(junit::TestsuiteProperty { name: name.to_string(), value: value.to_string() })
+	}
+}
+
+/// Keeps the failure of the last attempt of a test that is retried.
+fn retried(testcase: &mut junit::TestsuiteTestcase) {
+	if let Some(junit::TestsuiteTestcaseStatus::Failure { r#type, message, system_output }) = testcase.status.take() {
+		testcase.rerun_failures.push(junit::TestsuiteTestcaseRerun { r#type, message, system_output });
+	}
+}
+
+fn no_suite() -> Error {
+	Error::convert("test event outside of a test suite")
+}
+
+/// Returns the position of the open suite of a test binary, or of the last open suite, if the binary is
+/// not known, as nextest only names it with `--message-format libtest-json-plus`.
+fn find_suite<'a>(mut binaries: impl ExactSizeIterator<Item = &'a str>, binary: &str) -> Option<usize> {
+	let len = binaries.len();
+	binaries.position(|v| v == binary).or_else(|| len.checked_sub(1))
+}
+
+/// The error for security reports of a scan type, that the vulnerabilities cannot be located for.
+fn unsupported_scan_type(ty: gitlab_security_report::ScanType) -> Error {
+	Error::convert(format!("security reports of type `{:?}` are not supported, only SAST and dependency scanning", ty))
+}
+
+fn audit_issue_to_gitlab_vuln(issue: audit::Issue, ty: gitlab_security_report::ScanType) -> Result<gitlab_security_report::Vulnerability, Error> {
+	Ok(gitlab_security_report::Vulnerability {
 		category:    "Dependency Scanning".to_string(),
 		severity:    Some(match issue.kind.as_deref() {
 			None           => gitlab_security_report::VulnerabilitySeverity::High,
@@ -384,13 +1155,13 @@ fn audit_issue_to_gitlab_vuln(issue: audit::Issue, ty: gitlab_security_report::S
 				module:     None,
 				item:       None
 			},
-			_ => unreachable!()
+			ty => return Err(unsupported_scan_type(ty))
 		},
 		..Default::default()
-	}
+	})
 }
 
-fn geiger_package_to_gitlab_vuln(package: geiger::Package, ty: gitlab_security_report::ScanType) -> gitlab_security_report::Vulnerability {
+fn geiger_package_to_gitlab_vuln(package: geiger::Package, ty: gitlab_security_report::ScanType) -> Result<gitlab_security_report::Vulnerability, Error> {
 	let unsafe_used = package.unsafety.used.functions.unsafe_
 		+ package.unsafety.used.exprs.unsafe_
 		+ package.unsafety.used.item_impls.unsafe_
@@ -402,7 +1173,7 @@ fn geiger_package_to_gitlab_vuln(package: geiger::Package, ty: gitlab_security_r
 		+ package.unsafety.unused.item_traits.unsafe_
 		+ package.unsafety.unused.methods.unsafe_;
 	
-	gitlab_security_report::Vulnerability {
+	Ok(gitlab_security_report::Vulnerability {
 		category:    "Dependency Scanning".to_string(),
 		severity:    Some(gitlab_security_report::VulnerabilitySeverity::Info),
 		name:        Some(format!("Unsafe usage in package `{}`", package.package.id.name)),
@@ -439,8 +1210,304 @@ Methods:  {}/{}
 				module:     None,
 				item:       None
 			},
-			_ => unreachable!()
+			ty => return Err(unsupported_scan_type(ty))
 		},
 		..Default::default()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn junit(input: &str) -> junit::Report {
+		let messages = read_messages::<cargo::CargoMessage, _>(input.as_bytes()).map(Result::unwrap);
+		TestToJunit::default()
+			.with_environment(SuiteEnvironment { hostname: "localhost".to_string(), timestamp: None, properties: Vec::new() })
+			.convert(messages)
+			.unwrap()
+	}
+	
+	fn metrics(input: &str) -> String {
+		let messages = read_messages::<cargo::CargoMessage, _>(input.as_bytes()).map(Result::unwrap);
+		write(&TestToOpenMetrics::default().convert(messages).unwrap())
+	}
+	
+	fn write(report: &dyn WriteReport) -> String {
+		let mut out = Vec::new();
+		report.write_report(&mut out).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+	
+	fn failures(attempts: &[junit::TestsuiteTestcaseRerun]) -> Vec<&str> {
+		attempts.iter().filter_map(|v| v.message.as_deref()).collect()
+	}
+	
+	/// The fixture is hand-written after the `libtest-json-plus` output of `cargo nextest run --retries 2`, with the
+	/// suites of two test binaries running at the same time.
+	#[test]
+	fn nextest_retries() {
+		let report = junit(include_str!("../tests/fixtures/nextest-retries.json"));
+		let names  = report.0.iter().map(|suite| suite.name.as_str()).collect::<Vec<_>>();
+		assert_eq!(names, ["my-crate::lib (unittests)", "my-crate::integration"]);
+		
+		let flaky = &report.0[0].testcases.as_ref().unwrap()[1];
+		assert_eq!(flaky.name, "flaky");
+		assert!(flaky.status.is_none());
+		assert_eq!(failures(&flaky.flaky_failures), ["attempt 1"]);
+		assert!(flaky.rerun_failures.is_empty());
+		
+		let broken = &report.0[1].testcases.as_ref().unwrap()[0];
+		assert_eq!(broken.name, "broken");
+		assert!(matches!(&broken.status, Some(junit::TestsuiteTestcaseStatus::Failure { message: Some(message), .. }) if message == "attempt 3"));
+		assert_eq!(failures(&broken.rerun_failures), ["attempt 1", "attempt 2"]);
+		assert!(broken.flaky_failures.is_empty());
+	}
+	
+	#[test]
+	fn nextest_plain_suite_names() {
+		let report = junit(concat!(
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"test","event":"started","name":"my-crate::integration$flaky"}"#, "\n",
+			r#"{"type":"test","event":"failed","name":"my-crate::integration$flaky","stdout":"thread 'flaky' panicked at tests/integration.rs:3:5:\nnope\n"}"#, "\n",
+			// a retry that is not started again
+			r#"{"type":"test","event":"ok","name":"my-crate::integration$flaky"}"#, "\n",
+			r#"{"type":"suite","event":"ok","passed":1,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0.1}"#, "\n",
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"test","event":"started","name":"my-crate$tests::parse"}"#, "\n",
+			r#"{"type":"test","event":"ok","name":"my-crate$tests::parse"}"#, "\n",
+			r#"{"type":"suite","event":"ok","passed":1,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0.1}"#, "\n"
+		));
+		let names = report.0.iter().map(|suite| suite.name.as_str()).collect::<Vec<_>>();
+		assert_eq!(names, ["my-crate::integration", "my-crate::lib (unittests)"]);
+		
+		let testcases = report.0[0].testcases.as_ref().unwrap();
+		assert_eq!(testcases.len(), 1);
+		assert_eq!(failures(&testcases[0].flaky_failures), ["nope"]);
+	}
+	
+	#[test]
+	fn verbose_suite_names() {
+		let suite = concat!(
+			r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+			r#"{"type":"test","event":"started","name":"tests::it_works"}"#, "\n",
+			r#"{"type":"test","event":"ok","name":"tests::it_works"}"#, "\n",
+			r#"{"type":"suite","event":"ok","passed":1,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0.1}"#, "\n"
+		);
+		
+		// the unittests of the library and the binary of `my_crate`, which share the name of their target
+		let input = [
+			"     Running `/build/target/debug/deps/my_crate-0123456789abcdef`\n", suite,
+			"     Running `/build/target/debug/deps/my_crate-fedcba9876543210`\n", suite,
+			"     Running `/build/target/debug/deps/my_crate-0123456789abcdef --ignored`\n", suite
+		].concat();
+		
+		let report = junit(&input);
+		let names  = report.0.iter().map(|suite| suite.name.as_str()).collect::<Vec<_>>();
+		assert_eq!(names, ["my_crate", "my_crate (fedcba9876543210)", "my_crate"]);
+		
+		let metrics = metrics(&input);
+		assert!(metrics.contains(r#"suite="my_crate (fedcba9876543210)""#), "{}", metrics);
+	}
+	
+	const UNFINISHED: &str = concat!(
+		r#"{"type":"suite","event":"started","test_count":3}"#, "\n",
+		r#"{"type":"test","event":"started","name":"tests::passes"}"#, "\n",
+		r#"{"type":"test","event":"ok","name":"tests::passes","exec_time":0.5}"#, "\n",
+		r#"{"type":"test","event":"started","name":"tests::hangs"}"#, "\n",
+		r#"{"type":"test","event":"timeout","name":"tests::hangs"}"#, "\n",
+		r#"{"type":"test","event":"started","name":"tests::runs"}"#, "\n"
+	);
+	
+	fn statuses(suite: &junit::Testsuite) -> Vec<(&str, &str, &str)> {
+		suite.testcases.iter().flatten()
+			.filter_map(|v| match &v.status {
+				Some(junit::TestsuiteTestcaseStatus::Error { r#type, message }) => Some((v.name.as_str(), r#type.as_str(), message.as_str())),
+				_ => None
+			})
+			.collect()
 	}
-}
\ No newline at end of file
+	
+	#[test]
+	fn crashed_suite() {
+		let report = junit(UNFINISHED);
+		let suite  = &report.0[0];
+		
+		assert_eq!((suite.tests, suite.failures, suite.errors, suite.skipped), (3, 0, 2, 0));
+		assert_eq!(statuses(suite), [
+			("hangs", "crash", "the test ran for over 60 seconds and did not finish, the test binary was killed or crashed"),
+			("runs", "crash", "the test did not finish, the test binary was killed or crashed")
+		]);
+	}
+	
+	#[test]
+	fn interrupted_suite() {
+		let mut converter = TestToJunit::default()
+			.with_environment(SuiteEnvironment { hostname: "localhost".to_string(), timestamp: None, properties: Vec::new() });
+		
+		for msg in read_messages::<cargo::CargoMessage, _>(UNFINISHED.as_bytes()) {
+			converter.push(msg.unwrap()).unwrap();
+		}
+		
+		converter.interrupt("killed: job timeout");
+		let report = converter.finish().unwrap();
+		let suite  = &report.0[0];
+		
+		assert_eq!((suite.tests, suite.failures, suite.errors, suite.skipped), (3, 0, 2, 0));
+		assert_eq!(suite.time, 0.5);
+		assert_eq!(statuses(suite), [("hangs", "killed", "killed: job timeout"), ("runs", "killed", "killed: job timeout")]);
+		
+		let mut out = Vec::new();
+		report.write_report(&mut out).unwrap();
+		let xml = String::from_utf8(out).unwrap();
+		assert!(xml.contains(r#"errors="2""#), "{}", xml);
+		assert!(xml.contains(r#"<property name="error" value="killed: job timeout"/>"#), "{}", xml);
+		assert!(xml.contains(r#"<error type="killed" message="killed: job timeout"/>"#), "{}", xml);
+	}
+	
+	#[test]
+	fn checkpoint() {
+		let dir  = std::env::temp_dir().join(format!("gitlab-report-checkpoint-{}", std::process::id()));
+		let path = dir.join("results.xml");
+		let path = path.to_str().unwrap();
+		let read = || std::fs::read_to_string(path).unwrap();
+		std::fs::create_dir_all(&dir).unwrap();
+		
+		let mut converter = FanOut(vec![Box::new(TestToJunit::default()
+			.with_environment(SuiteEnvironment { hostname: "localhost".to_string(), timestamp: None, properties: Vec::new() }))]);
+		let mut messages  = read_messages::<cargo::CargoMessage, _>(UNFINISHED.as_bytes()).map(Result::unwrap);
+		
+		for msg in messages.by_ref().take(3) {
+			converter.push(msg).unwrap();
+		}
+		
+		assert!(converter.write_checkpoint([Some(path), None]).is_empty());
+		assert!(read().contains(r#"<testcase name="passes""#));
+		assert!(!read().contains(r#"<testcase name="hangs""#));
+		assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+		
+		// the next checkpoint replaces the previous one
+		for msg in messages {
+			converter.push(msg).unwrap();
+		}
+		
+		assert!(converter.write_checkpoint([Some(path)]).is_empty());
+		assert!(read().contains(r#"<testcase name="hangs""#));
+		
+		// the previous checkpoint is kept, if the temporary file cannot be written
+		let previous = read();
+		std::fs::create_dir(format!("{}.tmp", path)).unwrap();
+		assert_eq!(converter.write_checkpoint([Some(path)]).len(), 1);
+		assert_eq!(read(), previous);
+		
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+	
+	#[test]
+	fn crate_metrics() {
+		let suite = |binary: &str, tests: &[(&str, &str, f64)]| {
+			let mut suite = r#"{"type":"suite","event":"started","test_count":0}"#.to_string() + "\n";
+			
+			for (name, event, time) in tests {
+				suite += &format!(r#"{{"type":"test","event":"started","name":"{}${}"}}"#, binary, name);
+				suite += &format!("\n{{\"type\":\"test\",\"event\":\"{}\",\"name\":\"{}${}\",\"exec_time\":{}}}\n", event, binary, name, time);
+			}
+			
+			suite + r#"{"type":"suite","event":"ok","passed":0,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":1}"# + "\n"
+		};
+		let input = [
+			suite("alpha", &[("fast", "ok", 0.0009765625), ("medium", "ok", 0.25), ("slow", "failed", 2.0), ("skip", "ignored", 0.0)]),
+			suite("alpha::it", &[("fast", "ok", 0.0078125), ("timeout", "failed", 61.0)]),
+			suite("beta", &[("fast", "ok", 0.0625)])
+		].concat();
+		
+		// ignored tests are counted, but have no duration
+		let metrics = metrics(&input);
+		let crates  = metrics.lines().filter(|v| v.starts_with("crate_") && !v.contains(r#"crate="beta",le="#)).collect::<Vec<_>>();
+		assert_eq!(crates, [
+			r#"crate_tests_passed_total{crate="alpha"} 3"#,
+			r#"crate_tests_passed_total{crate="beta"} 1"#,
+			r#"crate_tests_failed_total{crate="alpha"} 2"#,
+			r#"crate_tests_failed_total{crate="beta"} 0"#,
+			r#"crate_tests_ignored_total{crate="alpha"} 1"#,
+			r#"crate_tests_ignored_total{crate="beta"} 0"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="0.001"} 1"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="0.01"} 2"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="0.1"} 2"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="0.5"} 3"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="1.0"} 3"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="5.0"} 4"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="10.0"} 4"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="30.0"} 4"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="60.0"} 4"#,
+			r#"crate_test_duration_seconds_bucket{crate="alpha",le="+Inf"} 5"#,
+			r#"crate_test_duration_seconds_sum{crate="alpha"} 63.2587890625"#,
+			r#"crate_test_duration_seconds_count{crate="alpha"} 5"#,
+			r#"crate_test_duration_seconds_sum{crate="beta"} 0.0625"#,
+			r#"crate_test_duration_seconds_count{crate="beta"} 1"#
+		]);
+	}
+	
+	fn diagnostic(package: &str, level: &str, code: Option<&str>, file: &str, rendered: &str) -> String {
+		serde_json::json!({
+			"reason": "compiler-message",
+			"package_id": package,
+			"message": {
+				"rendered": rendered,
+				"code": code.map(|code| serde_json::json!({ "code": code, "explanation": null })),
+				"level": level,
+				"message": rendered,
+				"spans": [{ "file_name": file, "line_start": 1, "line_end": 1, "column_start": 1, "column_end": 2, "is_primary": true }]
+			}
+		}).to_string()
+	}
+	
+	#[test]
+	fn clippy_metrics() {
+		let lint  = Some("clippy::needless_return");
+		let input = [
+			diagnostic("path+file:///ws/a#0.1.0", "warning", lint, "src/lib.rs", "unneeded `return` in lib.rs:3"),
+			// the same diagnostic of the tests of the library with `--all-targets`
+			diagnostic("path+file:///ws/a#0.1.0", "warning", lint, "src/lib.rs", "unneeded `return` in lib.rs:3"),
+			diagnostic("path+file:///ws/a#0.1.0", "warning", lint, "tests/it.rs", "unneeded `return` in it.rs:7"),
+			// the same rendered diagnostic of another package
+			diagnostic("b 0.1.0 (path+file:///ws/b)", "warning", lint, "src/lib.rs", "unneeded `return` in lib.rs:3"),
+			diagnostic("b 0.1.0 (path+file:///ws/b)", "error", None, "build.rs", "mismatched types"),
+			r#"{"reason":"compiler-message","package_id":"b 0.1.0 (path+file:///ws/b)","message":{"rendered":"aborting due to 1 previous error","code":null,"level":"error","message":"aborting due to 1 previous error","spans":[]}}"#.to_string()
+		].join("\n");
+		
+		let messages = read_messages::<clippy::Message, _>(input.as_bytes()).map(Result::unwrap);
+		let report   = ClippyToOpenMetrics::default().convert(messages).unwrap();
+		
+		assert_eq!(write(&report), concat!(
+			"# TYPE clippy_diagnostics gauge\n",
+			"# HELP clippy_diagnostics The number of diagnostics by their level, lint, crate and the top-level directory of their file.\n",
+			"clippy_diagnostics{level=\"error\",code=\"unknown\",crate=\"b\",dir=\".\"} 1\n",
+			"clippy_diagnostics{level=\"warning\",code=\"clippy::needless_return\",crate=\"a\",dir=\"src\"} 1\n",
+			"clippy_diagnostics{level=\"warning\",code=\"clippy::needless_return\",crate=\"a\",dir=\"tests\"} 1\n",
+			"clippy_diagnostics{level=\"warning\",code=\"clippy::needless_return\",crate=\"b\",dir=\"src\"} 1\n",
+			"# TYPE clippy_diagnostics_by_level gauge\n",
+			"# HELP clippy_diagnostics_by_level The number of diagnostics of each level.\n",
+			"clippy_diagnostics_by_level{level=\"error\"} 1\n",
+			"clippy_diagnostics_by_level{level=\"warning\"} 3\n",
+			"# TYPE clippy_lints gauge\n",
+			"# HELP clippy_lints The number of distinct lints with diagnostics.\n",
+			"clippy_lints 1\n",
+			"# EOF\n"
+		));
+	}
+	
+	#[test]
+	fn unsupported_scan_type() {
+		let report = audit::Report {
+			vulnerabilities: audit::SettingsVulnerabilities { found: true, count: 1, list: vec![audit::Issue::default()] },
+			..Default::default()
+		};
+		
+		let mut converter = AuditToGitlabSecurityReport::new(gitlab_security_report::ScanType::Dast);
+		assert!(matches!(converter.push(report), Err(Error::Convert { .. })));
+		
+		let mut converter = AuditToGitlabSecurityReport::new(gitlab_security_report::ScanType::DependencyScanning);
+		assert!(converter.push(audit::Report::default()).is_ok());
+	}
+}
@@ -37,6 +37,18 @@ pub struct Report {
 	pub dependency_files: Vec<DependencyFile>
 }
 
+impl WriteReport for Report {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		serde_json::to_writer(writer, self).map_err(io::Error::from)
+	}
+}
+
+impl Findings for Report {
+	fn findings(&self) -> usize {
+		self.vulnerabilities.len()
+	}
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Scan {
 	pub start_time: String, // ISO8601
@@ -118,13 +130,19 @@ pub struct Vulnerability {
 	pub raw_source_code_extract: Option<String>
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum VulnerabilitySeverity {
+	#[serde(alias = "info")]
 	Info,
+	#[serde(alias = "unknown")]
 	Unknown,
+	#[serde(alias = "low")]
 	Low,
+	#[serde(alias = "medium")]
 	Medium,
+	#[serde(alias = "high")]
 	High,
+	#[serde(alias = "critical")]
 	Critical
 }
 
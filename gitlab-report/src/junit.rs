@@ -30,6 +30,65 @@ use super::*;
 #[serde(rename = "testsuites")]
 pub struct Report(pub Vec<Testsuite>);
 
+impl WriteReport for Report {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		writeln!(writer, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+		quick_xml::se::to_writer(XmlChars(writer), self)
+			.map_err(io::Error::other)
+	}
+}
+
+/// Removes the control characters that are not allowed in XML, like the escape sequences of colored output,
+/// from the bytes written to the inner writer. These are all single bytes in UTF-8.
+struct XmlChars<W>(W);
+
+impl<W: io::Write> io::Write for XmlChars<W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let mut start = 0;
+		
+		for (i, &byte) in buf.iter().enumerate() {
+			if byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r') {
+				self.0.write_all(&buf[start..i])?;
+				start = i + 1;
+			}
+		}
+		
+		self.0.write_all(&buf[start..])?;
+		Ok(buf.len())
+	}
+	
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+/// Keeps the first `head` and the last `tail` bytes of an output that is longer, rounded to whole characters,
+/// and replaces the rest with a marker.
+pub fn truncate(output: &str, head: usize, tail: usize) -> String {
+	if output.len() <= head.saturating_add(tail) {
+		return output.to_string();
+	}
+	
+	let mut start = head;
+	let mut end   = output.len() - tail;
+	
+	while !output.is_char_boundary(start) {
+		start -= 1;
+	}
+	
+	while !output.is_char_boundary(end) {
+		end += 1;
+	}
+	
+	format!("{}\n… {} bytes omitted …\n{}", &output[..start], end - start, &output[end..])
+}
+
+impl Findings for Report {
+	fn findings(&self) -> usize {
+		self.0.iter().map(|suite| suite.failures + suite.errors).sum()
+	}
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 #[serde(rename = "testsuite")]
 pub struct Testsuite {
@@ -42,43 +101,122 @@ pub struct Testsuite {
 	pub errors:     usize,
 	pub skipped:    usize,
 	pub time:       f64,
-	#[serde(rename = "property")]
-	pub properties: Option<Vec<TestsuiteProperty>>,
+	pub properties: Option<TestsuiteProperties>,
 	#[serde(rename = "testcase")]
 	pub testcases:  Option<Vec<TestsuiteTestcase>>
 }
 
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TestsuiteProperties {
+	pub property: Vec<TestsuiteProperty>
+}
+
 #[derive(Clone, Debug, Default, Serialize)]
 pub struct TestsuiteProperty {
 	pub name:  String,
 	pub value: String
 }
 
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Default, Serialize)]
 #[serde(rename = "testcase")]
 pub struct TestsuiteTestcase {
 	pub status:    Option<TestsuiteTestcaseStatus>,
 	pub name:      String,
 	pub classname: String,
-	pub time:      f64
+	pub time:      f64,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub file:      Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub line:      Option<usize>,
+	/// Failed attempts of a test that passed when it was retried.
+	#[serde(rename = "flakyFailure", skip_serializing_if = "Vec::is_empty")]
+	pub flaky_failures: Vec<TestsuiteTestcaseRerun>,
+	/// Failed attempts of a test that failed when it was retried as well.
+	#[serde(rename = "rerunFailure", skip_serializing_if = "Vec::is_empty")]
+	pub rerun_failures: Vec<TestsuiteTestcaseRerun>,
+	#[serde(rename = "system-out", skip_serializing_if = "Option::is_none")]
+	pub system_out:     Option<TestsuiteTestcaseOutput>
 }
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TestsuiteTestcaseStatus {
-	Skipped,
+	Skipped {
+		#[serde(skip_serializing_if = "Option::is_none")]
+		message: Option<String>
+	},
 	//Error(#[serde(flatten)] TestsuiteTestcaseErrorOrFailure),
 	//Failure(#[serde(flatten)] TestsuiteTestcaseErrorOrFailure)
 	Error { r#type:  String, message: String },
 	Failure { 
         r#type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
         #[serde(rename = "$value")]
         system_output: String
     }
 }
 
+#[derive(Clone, Debug, Serialize)]
+pub struct TestsuiteTestcaseRerun {
+	pub r#type:        String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub message:       Option<String>,
+	#[serde(rename = "$value")]
+	pub system_output: String
+}
+
+/// The output of a test, GitLab shows the files referenced as `[[ATTACHMENT|path]]` in it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TestsuiteTestcaseOutput {
+	#[serde(rename = "$value")]
+	pub content: String
+}
+
+impl TestsuiteTestcaseOutput {
+	/// Adds a reference to a file that GitLab shows along with the test.
+	pub fn attach(&mut self, path: &str) {
+		if !self.content.is_empty() && !self.content.ends_with('\n') {
+			self.content.push('\n');
+		}
+		
+		self.content.push_str(&format!("[[ATTACHMENT|{}]]\n", path));
+	}
+}
+
 //#[derive(Clone, Debug, Serialize)]
 //pub struct TestsuiteTestcaseErrorOrFailure {
 //	pub r#type:  String,
 //	pub message: String
-//}
\ No newline at end of file
+//}
+#[cfg(test)]
+mod tests {
+	use {super::*, io::Write};
+	
+	#[test]
+	fn truncate_at_limit() {
+		let (head, tail) = (config::DEFAULT_OUTPUT_HEAD, config::DEFAULT_OUTPUT_TAIL);
+		let output = "a".repeat(head + tail);
+		assert_eq!(truncate(&output, head, tail), output);
+		
+		let output    = format!("{}{}{}", "h".repeat(head), "x".repeat(10), "t".repeat(tail));
+		let truncated = truncate(&output, head, tail);
+		assert_eq!(truncated, format!("{}\n… 10 bytes omitted …\n{}", "h".repeat(head), "t".repeat(tail)));
+	}
+	
+	#[test]
+	fn truncate_multibyte() {
+		// `ä` is two bytes, `€` three, the cuts fall within them and are moved outwards to the omitted part
+		let output = "aä€€ääa";
+		assert_eq!(truncate(output, 2, 2), "a\n… 12 bytes omitted …\na");
+		assert_eq!(truncate(output, 3, 4), "aä\n… 8 bytes omitted …\näa");
+		assert_eq!(truncate(output, 0, 0), "\n… 14 bytes omitted …\n");
+	}
+	
+	#[test]
+	fn strip_control_chars() {
+		let mut out = Vec::new();
+		XmlChars(&mut out).write_all(b"\x1b[31merror\x1b[0m:\tfailed\r\n\x00\x07\x7fdone \xe2\x82\xac").unwrap();
+		assert_eq!(out, b"[31merror[0m:\tfailed\r\n\x7fdone \xe2\x82\xac");
+	}
+}
@@ -0,0 +1,282 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Generates GitLab reports from cargo's JSON output.
+//!
+//! Each conversion is a [`Converter`] that is fed the messages of a cargo tool one at a time
+//! and produces a typed report, which can be written with [`WriteReport`].
+
+#![forbid(unsafe_code)]
+#![warn(clippy::all)]
+#![allow(clippy::from_over_into)]
+
+pub mod generate;
+pub mod cargo;
+pub mod clippy;
+pub mod audit;
+pub mod geiger;
+pub mod fmt;
+pub mod junit;
+pub mod code_climate;
+pub mod gitlab_security_report;
+pub mod open_metrics;
+pub mod error;
+pub mod detect;
+pub mod command;
+pub mod echo;
+pub mod config;
+pub mod merge;
+
+pub use error::{Error, Stage, Warning};
+
+use {std::{io, collections::HashMap, marker::PhantomData}, serde::*};
+
+/// A conversion from the output of a cargo tool into a GitLab report.
+pub trait Converter {
+	/// A single message of the tool's output.
+	type Input;
+	/// The generated report.
+	type Output;
+	
+	/// Processes a single message.
+	fn push(&mut self, input: Self::Input) -> Result<(), Error>;
+	
+	/// Notes that the input ended prematurely, because the process was terminated, e.g. by a job timeout.
+	/// Called before [`Converter::finish`], does nothing by default.
+	fn interrupt(&mut self, reason: &str) {
+		let _ = reason;
+	}
+	
+	/// Finishes the conversion after the last message and returns the report.
+	fn finish(self) -> Result<Self::Output, Error>;
+	
+	/// Processes all messages and returns the report.
+	fn convert(mut self, input: impl IntoIterator<Item = Self::Input>) -> Result<Self::Output, Error> where Self: Sized {
+		for msg in input {
+			self.push(msg)?;
+		}
+		
+		self.finish()
+	}
+}
+
+/// A report that can be written in the format GitLab expects.
+pub trait WriteReport {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()>;
+}
+
+/// A report that may contain findings, such as failed tests, lints or vulnerabilities.
+pub trait Findings {
+	/// Returns the number of findings in the report.
+	fn findings(&self) -> usize;
+}
+
+/// A report of any format, as produced by a [`DynConverter`].
+pub trait AnyReport: WriteReport + Findings {}
+
+impl<T: WriteReport + Findings> AnyReport for T {}
+
+/// An object safe [`Converter`] with a type-erased report, so that converters with different
+/// reports can be used together.
+pub trait DynConverter<I> {
+	fn push_dyn(&mut self, input: I) -> Result<(), Error>;
+	
+	fn interrupt_dyn(&mut self, reason: &str);
+	
+	/// Returns the report of the messages so far, without finishing the conversion.
+	fn snapshot_dyn(&self) -> Result<Box<dyn AnyReport>, Error>;
+	
+	fn finish_dyn(self: Box<Self>) -> Result<Box<dyn AnyReport>, Error>;
+}
+
+impl<C: Converter + Clone> DynConverter<C::Input> for C where C::Output: AnyReport + 'static {
+	fn push_dyn(&mut self, input: C::Input) -> Result<(), Error> {
+		self.push(input)
+	}
+	
+	fn interrupt_dyn(&mut self, reason: &str) {
+		self.interrupt(reason)
+	}
+	
+	fn snapshot_dyn(&self) -> Result<Box<dyn AnyReport>, Error> {
+		Ok(Box::new(self.clone().finish()?))
+	}
+	
+	fn finish_dyn(self: Box<Self>) -> Result<Box<dyn AnyReport>, Error> {
+		Ok(Box::new((*self).finish()?))
+	}
+}
+
+/// A message of a cargo tool, usually a line of JSON.
+pub trait Message: de::DeserializeOwned {
+	/// Parses a line that is not JSON, such as cargo's `Running ...` lines, which are skipped by default.
+	fn from_line(line: &str) -> Option<Self> {
+		let _ = line;
+		None
+	}
+}
+
+impl Message for clippy::Message {}
+
+/// Reads newline delimited JSON messages, as emitted by `cargo --message-format=json` or libtest's
+/// `--format json`.
+///
+/// Lines that are not valid messages, like cargo's `Running ...` lines or output of tests that bypasses
+/// the capture, are skipped and recorded as warnings, unless [`Messages::strict`] is enabled.
+pub fn read_messages<T: Message, R: io::BufRead>(reader: R) -> Messages<R, T> {
+	Messages { reader, line: 0, buf: String::new(), strict: false, passthrough: false, warnings: Vec::new(), _marker: PhantomData }
+}
+
+/// Reads a single JSON document, as emitted by `cargo audit --json` or `cargo geiger --output-format Json`.
+pub fn read_document<T: de::DeserializeOwned>(mut reader: impl io::BufRead) -> Result<T, Error> {
+	let mut buf = String::new();
+	reader.read_to_string(&mut buf).map_err(|source| Error::Io { stage: Stage::Parse, source })?;
+	serde_json::from_str(&buf).map_err(|source| Error::Parse {
+		line:    source.line(),
+		snippet: error::snippet(buf.lines().nth(source.line().saturating_sub(1)).unwrap_or_default()),
+		source
+	})
+}
+
+/// An iterator over newline delimited JSON messages.
+pub struct Messages<R, T> {
+	reader:      R,
+	line:        usize,
+	buf:         String,
+	strict:      bool,
+	passthrough: bool,
+	warnings:    Vec<Warning>,
+	_marker:     PhantomData<T>
+}
+
+impl<R, T> Messages<R, T> {
+	/// Fails on lines that are not valid messages, instead of skipping them.
+	pub fn strict(mut self, strict: bool) -> Self {
+		self.strict = strict;
+		self
+	}
+	
+	/// Prints lines that are not valid messages to STDERR as they are read, instead of skipping them, for
+	/// output that includes the human readable output of the command, such as cargo's STDERR. Strict mode
+	/// still fails on them.
+	pub fn passthrough(mut self, passthrough: bool) -> Self {
+		self.passthrough = passthrough;
+		self
+	}
+	
+	/// Returns the lines that were skipped so far.
+	pub fn warnings(&self) -> &[Warning] {
+		&self.warnings
+	}
+	
+	/// Returns the number of the line that was read last.
+	pub fn line(&self) -> usize {
+		self.line
+	}
+	
+	/// Returns the line that was read last.
+	pub fn raw(&self) -> &str {
+		&self.buf
+	}
+}
+
+impl<R: io::BufRead, T: Message> Iterator for Messages<R, T> {
+	type Item = Result<T, Error>;
+	
+	fn next(&mut self) -> Option<Self::Item> {
+		loop {
+			self.buf.clear();
+			
+			match self.reader.read_line(&mut self.buf) {
+				Ok(0) => return None,
+				Ok(_) => (),
+				Err(source) => return Some(Err(Error::Io { stage: Stage::Parse, source }))
+			}
+			
+			self.line += 1;
+			let len = self.buf.trim_end_matches(&['\n', '\r'][..]).len();
+			self.buf.truncate(len);
+			
+			if !self.strict && self.buf.trim().is_empty() {
+				continue;
+			}
+			
+			match serde_json::from_str(&self.buf).map_err(|e| (T::from_line(&self.buf), e)) {
+				Ok(v) | Err((Some(v), _)) => return Some(Ok(v)),
+				Err((_, source)) if self.strict => return Some(Err(Error::Parse {
+					line:    self.line,
+					snippet: error::snippet(&self.buf),
+					source
+				})),
+				Err((_, _)) if self.passthrough => eprintln!("{}", self.buf),
+				Err((_, e)) => self.warnings.push(Warning {
+					line:    self.line,
+					snippet: error::snippet(&self.buf),
+					message: e.to_string()
+				})
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	const INPUT: &str = concat!(
+		r#"{"type":"suite","event":"started","test_count":1}"#, "\n",
+		"   Compiling my-crate v0.1.0\n",
+		"\n",
+		"     Running unittests src/lib.rs (target/debug/deps/my_crate-0123456789abcdef)\n",
+		r#"{"type":"suite","event":"ok","passed":0,"failed":0,"ignored":0,"measured":0,"filtered_out":0,"exec_time":0.1}"#, "\n"
+	);
+	
+	#[test]
+	fn lenient_messages() {
+		let mut messages = read_messages::<cargo::CargoMessage, _>(INPUT.as_bytes());
+		let parsed = messages.by_ref().collect::<Result<Vec<_>, _>>().unwrap();
+		
+		assert_eq!(parsed.len(), 3);
+		assert!(matches!(parsed[1], cargo::CargoMessage::Running(_)));
+		assert_eq!(messages.warnings().iter().map(|v| v.line).collect::<Vec<_>>(), [2]);
+		assert_eq!(messages.line(), 5);
+	}
+	
+	#[test]
+	fn strict_messages() {
+		let mut messages = read_messages::<cargo::CargoMessage, _>(INPUT.as_bytes()).strict(true);
+		assert!(messages.next().unwrap().is_ok());
+		assert!(matches!(messages.next(), Some(Err(Error::Parse { line: 2, .. }))));
+	}
+	
+	#[test]
+	fn passthrough_messages() {
+		let mut messages = read_messages::<cargo::CargoMessage, _>(INPUT.as_bytes()).passthrough(true);
+		assert_eq!(messages.by_ref().map(Result::unwrap).count(), 3);
+		assert!(messages.warnings().is_empty());
+		
+		// strict mode is not relaxed by passing the lines through
+		let mut messages = read_messages::<cargo::CargoMessage, _>(INPUT.as_bytes()).passthrough(true).strict(true);
+		assert!(messages.next().unwrap().is_ok());
+		assert!(matches!(messages.next(), Some(Err(Error::Parse { line: 2, .. }))));
+	}
+}
@@ -22,126 +22,959 @@
 
 #![forbid(unsafe_code)]
 #![warn(clippy::all)]
-#![allow(dead_code, clippy::from_over_into)]
-
-mod generate;
-mod cargo;
-mod clippy;
-mod audit;
-mod geiger;
-mod fmt;
-mod junit;
-mod code_climate;
-mod gitlab_security_report;
-
-use {std::{io, collections::HashMap}, serde::*};
-
-const HELP: &str = r#"
-USAGE:
-	gitlab-report [options]
-
-DESCRIPTION:
-	Generates GitLab compitable reports from cargo JSON output.
-	
-	If no input file is specified, the messages will be read from STDIN.
-	If no output file is specified, the report will be written to STDOUT.
-	If no output format is specified, it will be inferred.
-
-OPTIONS:
-    -h, --help                   display this help
-    -i, --input-file <path>      input file
-    -o, --output-file <path>     output file
-    -p, --input-format <format>  input format, one of `test`, `clippy`, `bench`, `audit`, `geiger` or `fmt`
-    -f, --output-format <format> output format, one of `junit`, `code-quality`, `openmetrics`, `gl-sast` or `gl-dep-scan`
-    
+
+use {
+	std::io::{self, IsTerminal},
+	serde::de,
+	clap::{Parser, Subcommand, Args, ValueEnum, CommandFactory, error::ErrorKind},
+	gitlab_report::{*, detect::InputFormat}
+};
+
+const AFTER_HELP: &str = r#"EXIT STATUS:
+  0  the report was generated
+  1  invalid arguments or configuration
+  2  invalid input, a message could not be parsed or converted
+  3  I/O failure while reading the input or writing the report
+  4  the report contains findings, only with `--fail-on-findings`
+  
+  `run` exits with the status of the command, if it failed.
+
 EXAMPLES:
-	cargo test --no-fail-fast -- -Z unstable-options --format json | gitlab-report -p test > report.xml
-	cargo clippy --message-format=json | gitlab-report -p clippy > gl-code-quality-report.json
-	cargo bench -- -Z unstable-options --format json | gitlab-report -p bench > metrics.txt
-	cargo audit --json | gitlab-report -p audit -f gl-sast > gl-sast-report.json
-	cargo audit --output-format Json | gitlab-report -p geiger -f gl-sast > gl-sast-report.json
-"#;
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum InputFormat {
-	Test,
-	Clippy,
-	Bench,
-	Audit,
-	Geiger,
-	Fmt
-}
-
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+  cargo test --no-fail-fast -- -Z unstable-options --format json --report-time | gitlab-report -p test > report.xml
+  cargo clippy --message-format=json | gitlab-report -p clippy > gl-code-quality-report.json
+  cargo bench -- -Z unstable-options --format json | gitlab-report -p bench > metrics.txt
+  cargo audit --json | gitlab-report -p audit -f gl-sast > gl-sast-report.json
+  cargo geiger --output-format Json | gitlab-report -p geiger -f gl-sast > gl-sast-report.json
+  cargo test -- -Z unstable-options --format json | gitlab-report -p test -f junit=results.xml -f openmetrics=metrics.txt
+  gitlab-report run -- cargo +nightly test --workspace --no-fail-fast
+  gitlab-report run -f openmetrics=metrics.txt -- cargo clippy --all-targets
+  gitlab-report validate -i results.json
+  gitlab-report completions bash > /etc/bash_completion.d/gitlab-report"#;
+
+const EXIT_USAGE:    i32 = 1;
+const EXIT_INPUT:    i32 = 2;
+const EXIT_IO:       i32 = 3;
+const EXIT_FINDINGS: i32 = 4;
+
+/// How often the reports are written while the input is read, so that a killed job leaves partial reports.
+const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// How often signals are checked for while the input format is detected.
+const SIGNAL_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Generates GitLab compatible reports from cargo JSON output.
+///
+/// If no input file is specified, the messages will be read from STDIN.
+/// If no output file is specified, the report will be written to STDOUT.
+/// If no input format is specified, it will be inferred from the first messages of the input.
+/// If no output format is specified, the default output format of the input format will be used.
+#[derive(Debug, Parser)]
+#[command(name = "gitlab-report", version, after_help = AFTER_HELP, args_conflicts_with_subcommands = true)]
+struct Cli {
+	#[command(subcommand)]
+	command: Option<Command>,
+	#[command(flatten)]
+	convert: ConvertArgs
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+	/// Converts cargo JSON output into GitLab reports, the default if no subcommand is given
+	Convert(ConvertArgs),
+	/// Runs a cargo command with JSON output and converts it
+	///
+	/// The flags required for JSON output are added to the command, the report is written to the output
+	/// file, or the default file name of the command, and the status of the command is kept.
+	/// Supported commands are `cargo test`, `cargo clippy`, `cargo bench`, `cargo audit` and `cargo geiger`.
+	Run(RunArgs),
+	/// Merges reports of the same kind, e.g. from the jobs of a matrix, into one
+	///
+	/// Code quality issues of different reports are deduplicated by their fingerprint and location, identical
+	/// vulnerabilities by their identifiers. Test suites, and issues and vulnerabilities that do not occur in all
+	/// reports, are tagged with their origin, which is given as `ORIGIN=PATH`, or else is the directory of the
+	/// report relative to the directory that contains all reports, e.g. `stable/release/x86_64-unknown-linux-musl`
+	/// for `reports/stable/release/x86_64-unknown-linux-musl/results.xml`.
+	Merge(MergeArgs),
+	/// Checks that the input can be parsed and converted, without writing a report
+	Validate(ValidateArgs),
+	/// Prints shell completions
+	Completions {
+		shell: clap_complete::Shell
+	},
+	/// Prints the man page
+	Man
+}
+
+#[derive(Debug, Args)]
+struct ConvertArgs {
+	/// Input file
+	#[arg(short, long = "input-file", visible_alias = "input", value_name = "PATH")]
+	input:  Option<String>,
+	#[command(flatten)]
+	report: ReportArgs
+}
+
+#[derive(Debug, Args)]
+struct RunArgs {
+	#[command(flatten)]
+	report:  ReportArgs,
+	/// The cargo command to run
+	#[arg(last = true, required = true, value_name = "COMMAND")]
+	command: Vec<String>
+}
+
+#[derive(Debug, Args)]
+struct MergeArgs {
+	/// Reports to merge, JUnit, code quality or security reports
+	#[arg(required = true, value_name = "[ORIGIN=]PATH")]
+	inputs:           Vec<String>,
+	/// Output file
+	#[arg(short, long = "output-file", visible_alias = "output", value_name = "PATH")]
+	output:           Option<String>,
+	/// Exit with status 4 if the merged report contains failed tests, lints or vulnerabilities
+	#[arg(short = 'e', long)]
+	fail_on_findings: bool
+}
+
+#[derive(Debug, Args)]
+struct ValidateArgs {
+	/// Input file
+	#[arg(short, long = "input-file", visible_alias = "input", value_name = "PATH")]
+	input:        Option<String>,
+	/// Input format, one of `test`, `clippy`, `bench`, `audit`, `geiger` or `fmt`
+	#[arg(short = 'p', long, value_name = "FORMAT", value_parser = parse_input_format)]
+	input_format: Option<InputFormat>,
+	/// Output format, validates the conversion into this format
+	#[arg(short = 'f', long = "output-format", value_name = "FORMAT")]
+	outputs:      Vec<OutputFormat>,
+	/// Fail on lines that are not valid messages, instead of skipping them
+	#[arg(short, long)]
+	strict:       bool,
+	/// Configuration file
+	#[arg(short, long, value_name = "PATH")]
+	config:       Option<String>
+}
+
+#[derive(Debug, Args)]
+struct ReportArgs {
+	/// Input format, one of `test`, `clippy`, `bench`, `audit`, `geiger` or `fmt`
+	#[arg(short = 'p', long, value_name = "FORMAT", value_parser = parse_input_format)]
+	input_format:     Option<InputFormat>,
+	/// Output file
+	#[arg(short, long = "output-file", visible_alias = "output", value_name = "PATH")]
+	output:           Option<String>,
+	/// Output format, may be given multiple times to generate several reports from the same input,
+	/// each written to its own path
+	#[arg(short = 'f', long = "output-format", value_name = "FORMAT[=PATH]", value_parser = parse_output)]
+	outputs:          Vec<(OutputFormat, Option<String>)>,
+	/// Fail on lines that are not valid messages, instead of skipping them
+	#[arg(short, long)]
+	strict:           bool,
+	/// Print the consumed messages in a human readable form to STDERR
+	#[arg(long)]
+	echo:             bool,
+	/// Exit with status 4 if the report contains failed tests, lints or vulnerabilities
+	#[arg(short = 'e', long)]
+	fail_on_findings: bool,
+	/// Configuration file, defaults to the closest `.gitlab-report.toml`, or the
+	/// `[workspace.metadata.gitlab-report]` table of the closest `Cargo.toml`
+	#[arg(short, long, value_name = "PATH")]
+	config:           Option<String>
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
 enum OutputFormat {
 	Junit,
+	#[value(name = "code-quality")]
 	CodeClimate,
+	#[value(name = "openmetrics")]
 	OpenMetrics,
 	GlSast,
 	GlDepScan
 }
 
+impl OutputFormat {
+	fn name(self) -> &'static str {
+		match self {
+			Self::Junit       => "junit",
+			Self::CodeClimate => "code-quality",
+			Self::OpenMetrics => "openmetrics",
+			Self::GlSast      => "gl-sast",
+			Self::GlDepScan   => "gl-dep-scan"
+		}
+	}
+	
+	/// Returns the output formats that can be generated from an input format, the first one is the default.
+	fn supported(format: InputFormat) -> &'static [Self] {
+		match format {
+			InputFormat::Test    => &[Self::Junit, Self::OpenMetrics],
+			InputFormat::Nextest => &[Self::Junit, Self::OpenMetrics],
+			InputFormat::Clippy  => &[Self::CodeClimate, Self::OpenMetrics],
+			InputFormat::Bench   => &[Self::OpenMetrics],
+			InputFormat::Audit   => &[Self::GlSast, Self::GlDepScan],
+			InputFormat::Geiger  => &[Self::GlSast, Self::GlDepScan],
+			InputFormat::Fmt     => &[]
+		}
+	}
+}
+
+fn parse_input_format(s: &str) -> Result<InputFormat, String> {
+	s.parse()
+}
+
+fn parse_output(s: &str) -> Result<(OutputFormat, Option<String>), String> {
+	let (format, path) = match s.split_once('=') {
+		Some((_, "")) => return Err("the path after `=` is empty".to_string()),
+		Some((format, path)) => (format, Some(path.to_string())),
+		None                 => (s, None)
+	};
+	
+	Ok((OutputFormat::from_str(format, false).map_err(|_| format!(
+		"invalid output format `{}`, expected one of `junit`, `code-quality`, `openmetrics`, `gl-sast` or `gl-dep-scan`",
+		format))?, path))
+}
+
 fn main() {
-	let mut args = std::env::args();
-	let _name = args.next().unwrap();
-	let mut file_in    = None;
-	let mut file_out   = None;
-	let mut format_in  = None;
-	let mut format_out = None;
+	let cli = Cli::try_parse().unwrap_or_else(|e| {
+		let _ = e.print();
+		std::process::exit(if e.use_stderr() { EXIT_USAGE } else { 0 });
+	});
+	
+	let code = match cli.command {
+		None                                => convert(cli.convert),
+		Some(Command::Convert(args))        => convert(args),
+		Some(Command::Run(args))            => run(args),
+		Some(Command::Merge(args))          => merge(args),
+		Some(Command::Validate(args))       => validate(args),
+		Some(Command::Completions { shell }) => {
+			clap_complete::generate(shell, &mut Cli::command(), "gitlab-report", &mut io::stdout());
+			0
+		}
+		Some(Command::Man) => match clap_mangen::Man::new(Cli::command()).render(&mut io::stdout()) {
+			Ok(()) => 0,
+			Err(e) => {
+				eprintln!("error: failed to write man page: {}", e);
+				EXIT_IO
+			}
+		}
+	};
+	
+	std::process::exit(code);
+}
+
+fn convert(args: ConvertArgs) -> i32 {
+	let config = load_config(args.report.config.as_deref());
+	let input  = args.input.or_else(|| config.input.clone());
+	
+	if args.report.input_format.is_none() && args.report.outputs.is_empty() && input.is_none() && io::stdin().is_terminal() {
+		let _ = Cli::command().print_help();
+		return 0;
+	}
+	
+	let options = Options {
+		format_in:   args.report.input_format,
+		strict:      args.report.strict,
+		echo:        args.report.echo,
+		passthrough: false,
+		validate:    false
+	};
+	
+	let outputs = args.report.outputs.into_iter()
+		.map(|(format, path)| (Some(format), path))
+		.collect();
+	
+	let code = generate(open_input(input.as_deref()), outputs, args.report.output, options, &config, None);
+	findings_code(code, args.report.fail_on_findings)
+}
+
+fn run(args: RunArgs) -> i32 {
+	let config = load_config(args.report.config.as_deref());
+	let (program, cargo_args) = args.command.split_first()
+		.unwrap_or_else(|| usage_error(ErrorKind::MissingRequiredArgument, "missing command, specify it after `--`"));
+	let (format, cargo_args) = command::json_args(cargo_args).unwrap_or_else(|| usage_error(ErrorKind::InvalidValue,
+		"unsupported command, expected one of `cargo test`, `cargo nextest run`, `cargo clippy`, `cargo bench`, `cargo audit` or `cargo geiger`"));
+	
+	let output = args.report.output
+		.or_else(|| config.output(format).map(str::to_string))
+		.or_else(|| format.default_output_file().map(str::to_string));
+	// the test suites are named after cargo's `Running ...` lines, so its STDERR is read along with the
+	// messages, in the same pipe to keep the order, and passed through
+	let combined = matches!(format, InputFormat::Test | InputFormat::Nextest);
+	
+	if combined && args.report.strict {
+		usage_error(ErrorKind::ArgumentConflict, format!(
+			"`--strict` cannot be used with `{}`, whose human readable output is read along with its messages", args.command.join(" ")));
+	}
+	
+	let options  = Options {
+		format_in:   Some(args.report.input_format.unwrap_or(format)),
+		strict:      args.report.strict,
+		echo:        args.report.echo,
+		passthrough: combined,
+		validate:    false
+	};
+	
+	eprintln!("     \x1b[32;1mRunning\x1b[0m `{} {}`", program, cargo_args.join(" "));
+	let mut command = std::process::Command::new(program);
+	command.args(&cargo_args);
+	
+	// nextest's libtest compatible output is experimental
+	if format == InputFormat::Nextest {
+		command.env("NEXTEST_EXPERIMENTAL_LIBTEST_JSON", "1");
+	}
+	
+	let pipe = match combined {
+		true => {
+			let (reader, stdout, stderr) = io::pipe()
+				.and_then(|(reader, writer)| Ok((reader, writer.try_clone()?, writer)))
+				.unwrap_or_else(|e| {
+					eprintln!("error: failed to create pipe: {}", e);
+					std::process::exit(EXIT_IO);
+				});
+			command.stdout(stdout).stderr(stderr);
+			Some(reader)
+		}
+		false => {
+			command.stdout(std::process::Stdio::piped());
+			None
+		}
+	};
+	
+	let mut child = command.spawn().unwrap_or_else(|e| {
+		eprintln!("error: failed to run `{}`: {}", program, e);
+		std::process::exit(EXIT_IO);
+	});
+	
+	// the pipe only reaches its end once all writers are closed, including those of the command
+	drop(command);
+	
+	let reader: Box<dyn io::BufRead + Send> = match pipe {
+		Some(pipe) => Box::new(io::BufReader::new(pipe)),
+		None       => Box::new(io::BufReader::new(child.stdout.take().expect("stdout is piped")))
+	};
+	let outputs = args.report.outputs.into_iter()
+		.map(|(format, path)| (Some(format), path))
+		.collect();
+	let toolchain = command::toolchain(&cargo_args);
+	let code = findings_code(generate(reader, outputs, output, options, &config, toolchain), args.report.fail_on_findings);
+	
+	// the status of the wrapped command takes precedence, so that e.g. failed tests still fail the job
+	match child.wait() {
+		Ok(status) if !status.success() => status.code().unwrap_or(1),
+		Ok(_) => code,
+		Err(e) => {
+			eprintln!("error: failed to wait for command: {}", e);
+			EXIT_IO
+		}
+	}
+}
+
+fn merge(args: MergeArgs) -> i32 {
+	let sources = args.inputs.iter()
+		.map(|input| match input.split_once('=') {
+			Some((origin, path)) => (Some(origin.to_string()), path),
+			None                 => (None, input.as_str())
+		})
+		.collect::<Vec<_>>();
+	let origins = origins(&sources.iter().map(|(_, path)| *path).collect::<Vec<_>>());
+	let mut kind = None;
+	let mut reports = Vec::new();
+	
+	for ((origin, path), default_origin) in sources.into_iter().zip(origins) {
+		let content = std::fs::read_to_string(path).unwrap_or_else(|e| {
+			eprintln!("error: failed to read {}: {}", path, e);
+			std::process::exit(EXIT_IO);
+		});
+		
+		match (merge::ReportKind::detect(&content), kind) {
+			(None, _) => {
+				eprintln!("error: {} is not a JUnit, code quality or security report", path);
+				return EXIT_INPUT;
+			}
+			(Some(v), Some((first, first_path))) if v != first => usage_error(ErrorKind::ArgumentConflict, format!(
+				"cannot merge the {} report {} with the {} report {}", v.name(), path, first.name(), first_path)),
+			(Some(v), None) => kind = Some((v, path)),
+			_ => ()
+		}
+		
+		reports.push(merge::Source { origin: origin.unwrap_or(default_origin), content });
+	}
+	
+	let (kind, _) = kind.expect("at least one input is required");
+	let result = match kind {
+		merge::ReportKind::Junit          => merge::MergeJunit::default().convert(reports),
+		merge::ReportKind::CodeQuality    => merge::MergeCodeQuality::default().convert(reports),
+		merge::ReportKind::SecurityReport => merge::MergeSecurityReport::default().convert(reports)
+	};
+	let options = Options { format_in: None, strict: false, echo: false, passthrough: false, validate: false };
+	let name    = format!("merged {} report", kind.name());
+	let outputs = [(None, args.output)];
+	let result  = result.and_then(|report| write(vec![Box::new(report)], &[&name], &outputs, options));
+	
+	findings_code(result.map_err(|e| {
+		eprintln!("error: {}", e);
+		if let Error::Io { .. } = e { EXIT_IO } else { EXIT_INPUT }
+	}), args.fail_on_findings)
+}
+
+/// Returns the default origin of each report, the directory of the report relative to the
+/// directory that contains all reports, or the file name if all reports are in the same directory.
+fn origins(paths: &[&str]) -> Vec<String> {
+	let dirs = paths.iter()
+		.map(|path| std::path::Path::new(path).parent().unwrap_or_else(|| "".as_ref()).components().collect::<Vec<_>>())
+		.collect::<Vec<_>>();
+	let common = (0..dirs.iter().map(Vec::len).min().unwrap_or(0))
+		.take_while(|&i| dirs.iter().all(|dir| dir[i] == dirs[0][i]))
+		.count();
+	
+	paths.iter().zip(&dirs).map(|(path, dir)| match dir[common..].iter().collect::<std::path::PathBuf>() {
+		origin if origin.as_os_str().is_empty() => std::path::Path::new(path).file_stem()
+			.map_or_else(|| path.to_string(), |v| v.to_string_lossy().into_owned()),
+		origin => origin.to_string_lossy().replace('\\', "/")
+	}).collect()
+}
+
+fn validate(args: ValidateArgs) -> i32 {
+	let config  = load_config(args.config.as_deref());
+	let input   = args.input.or_else(|| config.input.clone());
+	let options = Options {
+		format_in:   args.input_format,
+		strict:      args.strict,
+		echo:        false,
+		passthrough: false,
+		validate:    true
+	};
+	
+	let outputs = args.outputs.into_iter()
+		.map(|format| (Some(format), Some(format!("<{}>", format.name()))))
+		.collect();
+	
+	findings_code(generate(open_input(input.as_deref()), outputs, None, options, &config, None), false)
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Options {
+	format_in:   Option<InputFormat>,
+	strict:      bool,
+	echo:        bool,
+	/// Print lines that are not messages, as the input includes the STDERR of the command.
+	passthrough: bool,
+	/// Only convert the input, without writing the reports.
+	validate:    bool
+}
+
+fn load_config(path: Option<&str>) -> config::Config {
+	match path {
+		Some(path) => config::Config::load(path.as_ref()),
+		None => std::env::current_dir()
+			.map_err(|source| Error::Io { stage: Stage::Config, source })
+			.and_then(|dir| config::Config::discover(&dir))
+			.map(|config| config.map(|(_, config)| config).unwrap_or_default())
+	}.unwrap_or_else(|e| {
+		eprintln!("error: {}", e);
+		std::process::exit(EXIT_USAGE);
+	})
+}
+
+fn open_input(path: Option<&str>) -> Box<dyn io::BufRead + Send> {
+	let reader: Box<dyn io::Read + Send> = match path {
+		Some(file) => Box::new(std::fs::File::open(file).unwrap_or_else(|e| {
+			eprintln!("error: failed to open input file: {}", e);
+			std::process::exit(EXIT_IO);
+		})),
+		None => Box::new(io::stdin())
+	};
+	
+	Box::new(io::BufReader::new(reader))
+}
+
+/// Converts the input into a report for each output, returns the number of findings or the exit status.
+///
+/// The toolchain is the one of the cargo command that produced the input, if it is known.
+fn generate(
+	reader:    Box<dyn io::BufRead + Send>,
+	outputs:   Vec<(Option<OutputFormat>, Option<String>)>,
+	file_out:  Option<String>,
+	options:   Options,
+	config:    &config::Config,
+	toolchain: Option<&str>
+) -> Result<usize, i32> {
+	let mut signals = Signals::catch();
+	let (format_in, reader): (_, Box<dyn io::BufRead + Send>) = match options.format_in {
+		Some(format) => (format, reader),
+		None => match detect_input(reader, &mut signals) {
+			Ok((Some(format), reader)) => {
+				eprintln!("    \x1b[32;1mDetected\x1b[0m {} input", format.name());
+				(format, reader)
+			}
+			Ok((None, _)) => match signals.pending {
+				Some(signal) => {
+					eprintln!("warning: terminated before the input format could be inferred, no report is written");
+					std::process::exit(128 + signal);
+				}
+				None => {
+					eprintln!("error: failed to infer the input format, specify it with `-p <format>`");
+					return Err(EXIT_INPUT);
+				}
+			}
+			Err(source) => {
+				eprintln!("error: {}", Error::Io { stage: Stage::Parse, source });
+				return Err(EXIT_IO);
+			}
+		}
+	};
+	
+	let mut outputs = outputs;
+	
+	if outputs.is_empty() {
+		outputs.push((None, None));
+	}
+	
+	// outputs without their own path are written to the output file, the configured file, or STDOUT
+	let file_out = file_out.or_else(|| config.output(format_in).map(str::to_string));
+	
+	for (_, path) in &mut outputs {
+		if path.is_none() {
+			*path = file_out.clone();
+		}
+	}
+	
+	for (i, (_, path)) in outputs.iter().enumerate() {
+		if outputs[..i].iter().any(|(_, other)| other == path) {
+			usage_error(ErrorKind::ArgumentConflict, format!(
+				"multiple reports would be written to {}, specify a path with `-f <format>=<path>`",
+				path.as_deref().unwrap_or("STDOUT")));
+		}
+	}
+	
+	let options = Options { format_in: Some(format_in), ..options };
+	let result  = match format_in {
+		InputFormat::Test | InputFormat::Nextest => process(reader, signals, &outputs, options, |format| match format {
+			OutputFormat::Junit       => Some(output(
+				generate::TestToJunit::default()
+					.with_config(config.clone())
+					.with_environment(generate::SuiteEnvironment::detect(toolchain)), "JUnit report")),
+			OutputFormat::OpenMetrics => Some(output(
+				generate::TestToOpenMetrics::default().with_config(config.clone()), "OpenMetrics report")),
+			_ => None
+		}),
+		InputFormat::Clippy => process(reader, signals, &outputs, options, |format| match format {
+			OutputFormat::CodeClimate => Some(output(
+				generate::ClippyToCodeQuality::default().with_config(config.clone()), "code quality report")),
+			OutputFormat::OpenMetrics => Some(output(
+				generate::ClippyToOpenMetrics::default().with_config(config.clone()), "OpenMetrics report")),
+			_ => None
+		}),
+		InputFormat::Bench  => process(reader, signals, &outputs, options, |format| match format {
+			OutputFormat::OpenMetrics => Some(output(
+				generate::BenchToOpenMetrics::default().with_config(config.clone()), "OpenMetrics report")),
+			_ => None
+		}),
+		InputFormat::Audit  => process_document(reader, signals, &outputs, options, |format| match format {
+			OutputFormat::GlSast    => Some(output(
+				generate::AuditToGitlabSecurityReport::new(gitlab_security_report::ScanType::Sast)
+					.with_config(config.clone()), "SAST report")),
+			OutputFormat::GlDepScan => Some(output(
+				generate::AuditToGitlabSecurityReport::new(gitlab_security_report::ScanType::DependencyScanning)
+					.with_config(config.clone()), "dependency scanning report")),
+			_ => None
+		}),
+		InputFormat::Geiger => process_document(reader, signals, &outputs, options, |format| match format {
+			OutputFormat::GlSast    => Some(output(
+				generate::GeigerToGitlabSecurityReport::new(gitlab_security_report::ScanType::Sast), "SAST report")),
+			OutputFormat::GlDepScan => Some(output(
+				generate::GeigerToGitlabSecurityReport::new(gitlab_security_report::ScanType::DependencyScanning), "dependency scanning report")),
+			_ => None
+		}),
+		InputFormat::Fmt    => usage_error(ErrorKind::InvalidValue, "no report can be generated from `fmt` input")
+	};
+	
+	result.map_err(|e| {
+		eprintln!("error: {}", e);
+		match e {
+			Error::Config { .. } => EXIT_USAGE,
+			Error::Io { .. }     => EXIT_IO,
+			_                    => EXIT_INPUT
+		}
+	})
+}
+
+fn findings_code(result: Result<usize, i32>, fail_on_findings: bool) -> i32 {
+	match result {
+		Ok(findings) if fail_on_findings && findings > 0 => {
+			eprintln!("error: the report contains {} findings", findings);
+			EXIT_FINDINGS
+		}
+		Ok(_)     => 0,
+		Err(code) => code
+	}
+}
+
+type Output<I> = (Box<dyn DynConverter<I>>, &'static str);
+
+fn output<C: Converter + Clone + 'static>(converter: C, name: &'static str) -> Output<C::Input> where C::Output: AnyReport + 'static {
+	(Box::new(converter), name)
+}
+
+/// Reads newline delimited messages and writes a report for each output, returns the number of findings.
+///
+/// The messages are read on another thread, so that the reports can be written while the input is still being
+/// read, every [`CHECKPOINT_INTERVAL`], and when the process is terminated, e.g. by a job timeout, in which case
+/// it exits with the status of the signal afterwards.
+fn process<I: Message + Clone + echo::Echo + Send + 'static>(
+	reader:  impl io::BufRead + Send + 'static,
+	signals: Signals,
+	outputs: &[(Option<OutputFormat>, Option<String>)],
+	options: Options,
+	select:  impl Fn(OutputFormat) -> Option<Output<I>>
+) -> Result<usize, Error> {
+	let (mut converter, names) = converters(outputs, options, select);
+	let (sender, receiver) = std::sync::mpsc::channel();
+	let messages = read_messages::<I, _>(reader).strict(options.strict).passthrough(options.passthrough);
+	
+	std::thread::spawn({
+		let sender = sender.clone();
+		move || read(messages, sender)
+	});
+	
+	let peeked         = signals.peeked;
+	let mut pending    = signals.forward(sender);
+	let mut line       = 0;
+	let mut checkpoint = std::time::Instant::now();
+	let mut changed    = false;
 	
 	loop {
-		match args.next().as_deref() {
-			Some("-h") | Some("--help")          => {
-				eprintln!("{}", HELP);
-				return;
+		// a signal that was caught while the input format was detected is handled once the peeked messages are converted
+		if line >= peeked {
+			if let Some(signal) = pending.take() {
+				return interrupted(converter, &names, outputs, options, signal);
 			}
-			Some("-i") | Some("--input")         => file_in    = Some(args.next().unwrap()),
-			Some("-o") | Some("--output")        => file_out   = Some(args.next().unwrap()),
-			Some("-p") | Some("--input-format")  => format_in  = Some(match &*args.next().unwrap() {
-				"test"   => InputFormat::Test,
-				"clippy" => InputFormat::Clippy,
-				"bench"  => InputFormat::Bench,
-				"audit"  => InputFormat::Audit,
-				"geiger" => InputFormat::Geiger,
-				"fmt"    => InputFormat::Fmt,
-				v => {
-					eprintln!("error: invalid input format: {}", v);
-					std::process::exit(1);
+		}
+		
+		// without changes, there is nothing to write and no need to wake up before the next event
+		let event = match changed {
+			true  => receiver.recv_timeout(CHECKPOINT_INTERVAL.saturating_sub(checkpoint.elapsed())),
+			false => receiver.recv().map_err(|_| std::sync::mpsc::RecvTimeoutError::Disconnected)
+		};
+		
+		match event {
+			Ok(Event::Message(msg)) => {
+				let (msg, msg_line, raw) = msg?;
+				line = msg_line;
+				
+				if options.echo {
+					// the echo is only informational, failing to print it must not fail the report
+					let _ = msg.echo(&mut io::stderr());
 				}
-			}),
-			Some("-f") | Some("--output-format") => format_out = Some(match &*args.next().unwrap() {
-				"junit"        => OutputFormat::Junit,
-				"code-quality" => OutputFormat::CodeClimate,
-				"openmetrics"  => OutputFormat::OpenMetrics,
-				"gl-sast"      => OutputFormat::GlSast,
-				"gl-dep-scan"  => OutputFormat::GlDepScan,
-				v => {
-					eprintln!("error: invalid output format: {}", v);
-					std::process::exit(1);
+				
+				converter.push(msg).map_err(|e| e.at(line, &raw))?;
+				
+				// the interval starts with the first change since the last checkpoint
+				if !changed {
+					checkpoint = std::time::Instant::now();
+					changed    = true;
 				}
-			}),
-			Some(v)                              => eprintln!("warning: unknown argument: {}", v),
-			None                                 => break,
+			}
+			Ok(Event::End(warnings)) => {
+				print_warnings(&warnings);
+				
+				if let Some(signal) = pending {
+					return interrupted(converter, &names, outputs, options, signal);
+				}
+				
+				break;
+			}
+			Ok(Event::Signal(signal, reason)) => return interrupted(converter, &names, outputs, options, (signal, reason)),
+			Err(std::sync::mpsc::RecvTimeoutError::Timeout) => (),
+			Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break
+		}
+		
+		if changed && checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+			write_checkpoint(&converter, outputs, options);
+			checkpoint = std::time::Instant::now();
+			changed    = false;
 		}
 	}
 	
-	if format_in.is_none() && format_out.is_none() {
-		eprintln!("{}", HELP);
-		return;
+	write(converter.finish()?, &names, outputs, options)
+}
+
+/// Writes the reports of the messages so far, after the process was terminated by a signal, and exits with the
+/// status of the signal.
+fn interrupted<I: Clone>(
+	mut converter: generate::FanOut<I>,
+	names:         &[&'static str],
+	outputs:       &[(Option<OutputFormat>, Option<String>)],
+	options:       Options,
+	(signal, reason): (i32, &'static str)
+) -> Result<usize, Error> {
+	eprintln!("warning: {}, writing the reports of the messages so far", reason);
+	converter.interrupt(reason);
+	write(converter.finish()?, names, outputs, options)?;
+	std::process::exit(128 + signal);
+}
+
+/// An event of the main loop of [`process`].
+enum Event<I> {
+	/// A message, with its line number and the line.
+	Message(Result<(I, usize, String), Error>),
+	/// The end of the input, with the lines that were skipped.
+	End(Vec<Warning>),
+	/// A signal that terminates the process, with the reason the tests were interrupted.
+	Signal(i32, &'static str)
+}
+
+/// Sends the messages to the main loop, stops at the first error.
+fn read<I: Message, R: io::BufRead>(mut messages: Messages<R, I>, sender: std::sync::mpsc::Sender<Event<I>>) {
+	while let Some(msg) = messages.next() {
+		let failed = msg.is_err();
+		
+		if sender.send(Event::Message(msg.map(|msg| (msg, messages.line(), messages.raw().to_string())))).is_err() || failed {
+			return;
+		}
 	}
 	
-	let reader: Box<dyn io::Read> = match file_in {
-		Some(file) => Box::new(match std::fs::File::open(file) {
-			 Ok(v) => v,
-			 Err(e) => {
-				 eprintln!("error: failed to open input file: {}", e);
-				 std::process::exit(1);
-			 }
-		 }),
-		None => Box::new(io::stdin())
+	let _ = sender.send(Event::End(messages.warnings().to_vec()));
+}
+
+/// Detects the input format on another thread, so that a signal, which is caught while the input is being
+/// peeked at, stops the detection with the lines that were peeked at so far, instead of waiting for the next one.
+fn detect_input(
+	reader:  Box<dyn io::BufRead + Send>,
+	signals: &mut Signals
+) -> io::Result<(Option<InputFormat>, Box<dyn io::BufRead + Send>)> {
+	use std::sync::{Arc, Mutex, PoisonError, mpsc::RecvTimeoutError};
+	
+	let detector = Arc::new(Mutex::new(detect::Detector::default()));
+	let (sender, receiver) = std::sync::mpsc::channel();
+	
+	std::thread::spawn({
+		let detector = detector.clone();
+		move || sender.send(detect::detect_shared(reader, &detector))
+	});
+	
+	let (format, reader): (_, Box<dyn io::BufRead + Send>) = loop {
+		match receiver.recv_timeout(SIGNAL_POLL_INTERVAL) {
+			Ok(result) => {
+				let (format, reader) = result?;
+				signals.peeked = reader.get_ref().0.get_ref().iter().filter(|&&b| b == b'\n').count();
+				break (format, Box::new(reader));
+			}
+			Err(RecvTimeoutError::Timeout) => (),
+			Err(RecvTimeoutError::Disconnected) => return Err(io::Error::other("input detection failed"))
+		}
+		
+		if let Some(signal) = signals.poll() {
+			let mut detector = detector.lock().unwrap_or_else(PoisonError::into_inner);
+			
+			// the detection finished in the meantime, its result is about to be sent
+			let buf = match detector.take() {
+				Some(buf) => buf,
+				None      => continue
+			};
+			
+			signals.peeked  = buf.iter().filter(|&&b| b == b'\n').count();
+			signals.pending = Some(signal);
+			break (detector.guess(), Box::new(io::Cursor::new(buf)));
+		}
 	};
 	
-	let writer: Box<dyn io::Write> = match file_out {
+	Ok((format, reader))
+}
+
+/// The signals that terminate the process, which are caught before the input format is detected, so
+/// that the reports are still written, if the process is terminated while the input is being peeked at.
+struct Signals {
+	#[cfg(unix)]
+	signals: Option<signal_hook::iterator::Signals>,
+	/// The number of lines that were peeked at to detect the input format.
+	peeked:  usize,
+	/// The signal that was caught while the input format was detected.
+	pending: Option<i32>
+}
+
+impl Signals {
+	#[cfg(unix)]
+	fn catch() -> Self {
+		use signal_hook::consts::{SIGINT, SIGTERM};
+		
+		match signal_hook::iterator::Signals::new([SIGTERM, SIGINT]) {
+			Ok(signals) => Self { signals: Some(signals), peeked: 0, pending: None },
+			Err(e)      => {
+				eprintln!("warning: failed to handle signals: {}", e);
+				Self { signals: None, peeked: 0, pending: None }
+			}
+		}
+	}
+	
+	#[cfg(not(unix))]
+	fn catch() -> Self {
+		Self { peeked: 0, pending: None }
+	}
+	
+	/// Returns the next signal that was caught, without waiting for one.
+	#[cfg(unix)]
+	fn poll(&mut self) -> Option<i32> {
+		self.signals.as_mut()?.pending().next()
+	}
+	
+	#[cfg(not(unix))]
+	fn poll(&mut self) -> Option<i32> {
+		None
+	}
+	
+	/// Sends the signals to the main loop, instead of terminating the process immediately, returns the signal
+	/// that was already caught, with the reason the tests were interrupted.
+	#[cfg(unix)]
+	fn forward<I: Send + 'static>(self, sender: std::sync::mpsc::Sender<Event<I>>) -> Option<(i32, &'static str)> {
+		use signal_hook::consts::SIGTERM;
+		
+		// GitLab terminates jobs that time out or are cancelled with SIGTERM
+		let reason = |signal| match signal {
+			SIGTERM => "killed: job timeout",
+			_       => "killed: interrupted"
+		};
+		
+		let mut signals = self.signals?;
+		let pending     = self.pending.or_else(|| signals.pending().next()).map(|signal| (signal, reason(signal)));
+		
+		std::thread::spawn(move || for signal in signals.forever() {
+			if sender.send(Event::Signal(signal, reason(signal))).is_err() {
+				break;
+			}
+		});
+		
+		pending
+	}
+	
+	#[cfg(not(unix))]
+	fn forward<I>(self, _sender: std::sync::mpsc::Sender<Event<I>>) -> Option<(i32, &'static str)> {
+		None
+	}
+	
+	/// Terminates the process on a signal, as if it was not caught, as no report can be written before the
+	/// whole input has been read.
+	#[cfg(unix)]
+	fn exit(self) {
+		let mut signals = match self.signals {
+			Some(signals) => signals,
+			None          => return
+		};
+		
+		std::thread::spawn(move || {
+			if let Some(signal) = signals.forever().next() {
+				std::process::exit(128 + signal);
+			}
+		});
+	}
+	
+	#[cfg(not(unix))]
+	fn exit(self) {}
+}
+
+/// Reads a single JSON document and writes a report for each output, returns the number of findings.
+fn process_document<I: de::DeserializeOwned + Clone + echo::Echo>(
+	reader:  impl io::BufRead,
+	signals: Signals,
+	outputs: &[(Option<OutputFormat>, Option<String>)],
+	options: Options,
+	select:  impl Fn(OutputFormat) -> Option<Output<I>>
+) -> Result<usize, Error> {
+	let (converter, names) = converters(outputs, options, select);
+	signals.exit();
+	let document = read_document::<I>(reader)?;
+	
+	if options.echo {
+		let _ = document.echo(&mut io::stderr());
+	}
+	
+	write(converter.convert(Some(document))?, &names, outputs, options)
+}
+
+fn converters<I>(
+	outputs: &[(Option<OutputFormat>, Option<String>)],
+	options: Options,
+	select:  impl Fn(OutputFormat) -> Option<Output<I>>
+) -> (generate::FanOut<I>, Vec<&'static str>) {
+	let format_in   = options.format_in.expect("input format is known");
+	let mut fan_out = generate::FanOut::default();
+	let mut names   = Vec::new();
+	
+	for &(format, _) in outputs {
+		let supported = OutputFormat::supported(format_in);
+		let format    = format.or_else(|| supported.first().copied());
+		let (converter, name) = format.and_then(&select).unwrap_or_else(|| usage_error(ErrorKind::ArgumentConflict, format!(
+			"{} reports cannot be generated from `{}` input, supported output formats are {}",
+			format.map_or("no", OutputFormat::name),
+			format_in.name(),
+			supported.iter().map(|v| format!("`{}`", v.name())).collect::<Vec<_>>().join(", "))));
+		
+		fan_out.0.push(converter);
+		names.push(name);
+	}
+	
+	(fan_out, names)
+}
+
+fn usage_error(kind: ErrorKind, message: impl std::fmt::Display) -> ! {
+	let _ = Cli::command().error(kind, message).print();
+	std::process::exit(EXIT_USAGE);
+}
+
+/// Prints the first few skipped lines and the number of the remaining ones.
+fn print_warnings(warnings: &[Warning]) {
+	const MAX_WARNINGS: usize = 10;
+	
+	for warning in warnings.iter().take(MAX_WARNINGS) {
+		eprintln!("warning: {}", warning);
+	}
+	
+	if warnings.len() > MAX_WARNINGS {
+		eprintln!("warning: skipped {} more invalid messages", warnings.len() - MAX_WARNINGS);
+	}
+}
+
+/// Writes each report to its output, returns the total number of findings.
+fn write(
+	reports: Vec<Box<dyn AnyReport>>,
+	names:   &[&str],
+	outputs: &[(Option<OutputFormat>, Option<String>)],
+	options: Options
+) -> Result<usize, Error> {
+	let mut findings = 0;
+	
+	for ((report, name), (_, path)) in reports.iter().zip(names).zip(outputs) {
+		findings += report.findings();
+		
+		if options.validate {
+			eprintln!("   \x1b[32;1mValidated\x1b[0m {} ({} findings)", name, report.findings());
+			continue;
+		}
+		
+		eprintln!("  \x1b[32;1mGenerating\x1b[0m {}", name);
+		
+		let mut writer = io::BufWriter::new(open_output(path.as_deref()));
+		report.write_report(&mut writer)
+			.and_then(|_| io::Write::flush(&mut writer))
+			.map_err(|source| Error::Io { stage: Stage::Write, source })?;
+	}
+	
+	Ok(findings)
+}
+
+/// Writes the reports of the messages so far to their files, see [`generate::FanOut::write_checkpoint`].
+fn write_checkpoint<I>(converter: &generate::FanOut<I>, outputs: &[(Option<OutputFormat>, Option<String>)], options: Options) {
+	if options.validate {
+		return;
+	}
+	
+	for (path, e) in converter.write_checkpoint(outputs.iter().map(|(_, path)| path.as_deref())) {
+		eprintln!("warning: failed to write checkpoint of {}: {}", path, e);
+	}
+}
+
+fn open_output(path: Option<&str>) -> Box<dyn io::Write> {
+	match path {
 		Some(file) => Box::new(match std::fs::OpenOptions::new()
 			.write(true)
 			.create(true)
@@ -151,32 +984,9 @@ fn main() {
 			 Ok(v) => v,
 			 Err(e) => {
 				 eprintln!("error: failed to open output file: {}", e);
-				 std::process::exit(1);
+				 std::process::exit(EXIT_IO);
 			 }
 		 }),
 		None => Box::new(io::stdout())
-	};
-	
-	let reader = io::BufReader::new(reader);
-	let writer = io::BufWriter::new(writer);
-	
-	match (format_in, format_out) {
-		(Some(InputFormat::Test),   None | Some(OutputFormat::Junit))       => generate::test_to_junit(reader, writer),
-		(Some(InputFormat::Test),   Some(OutputFormat::OpenMetrics))        => generate::test_to_open_metrics(reader, writer),
-		(Some(InputFormat::Clippy), None | Some(OutputFormat::CodeClimate)) => generate::clippy_to_code_quality(reader, writer),
-		(Some(InputFormat::Clippy), Some(OutputFormat::OpenMetrics))        => generate::clippy_to_open_metrics(reader, writer),
-		(Some(InputFormat::Bench),  None | Some(OutputFormat::OpenMetrics)) => generate::bench_to_open_metrics(reader, writer),
-		(Some(InputFormat::Audit),  None | Some(OutputFormat::GlSast))      => generate::audit_to_gitlab_security_report(gitlab_security_report::ScanType::Sast, reader, writer),
-		(Some(InputFormat::Audit),  Some(OutputFormat::GlDepScan))          => generate::audit_to_gitlab_security_report(gitlab_security_report::ScanType::DependencyScanning, reader, writer),
-		(Some(InputFormat::Geiger), None | Some(OutputFormat::GlSast))      => generate::geiger_to_gitlab_security_report(gitlab_security_report::ScanType::Sast, reader, writer),
-		(Some(InputFormat::Geiger), Some(OutputFormat::GlDepScan))          => generate::geiger_to_gitlab_security_report(gitlab_security_report::ScanType::DependencyScanning, reader, writer),
-		_ => {
-			eprintln!(
-				"error: invalid input and output format combination: {} -> {}",
-				format_in.map_or_else(|| "?".to_string(), |v| format!("{:?}", v)),
-				format_out.map_or_else(|| "?".to_string(), |v| format!("{:?}", v)),
-			);
-			std::process::exit(1);
-		}
 	}
 }
@@ -0,0 +1,466 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Merging of reports of the same kind, e.g. the reports of the jobs of a `parallel:matrix`.
+//!
+//! The reports are merged as they are, so that fields this crate does not know of, such as those of
+//! reports generated by other tools, are preserved.
+
+use {
+	super::*,
+	serde_json::Value,
+	quick_xml::events::{Event, BytesStart}
+};
+
+/// The kind of a report.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ReportKind {
+	Junit,
+	CodeQuality,
+	SecurityReport
+}
+
+impl ReportKind {
+	/// Infers the kind of a report from its content.
+	pub fn detect(content: &str) -> Option<Self> {
+		match content.trim_start().as_bytes().first()? {
+			b'<' => Some(Self::Junit),
+			b'[' => Some(Self::CodeQuality),
+			b'{' => Some(Self::SecurityReport),
+			_    => None
+		}
+	}
+	
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Junit          => "JUnit",
+			Self::CodeQuality    => "code quality",
+			Self::SecurityReport => "security"
+		}
+	}
+}
+
+/// A report, tagged with the job it originates from, e.g. `stable/release/x86_64-unknown-linux-musl`.
+#[derive(Clone, Debug)]
+pub struct Source {
+	pub origin:  String,
+	pub content: String
+}
+
+/// A merged report.
+#[derive(Clone, Debug, Default)]
+pub struct Merged {
+	pub content:  Vec<u8>,
+	pub findings: usize
+}
+
+impl WriteReport for Merged {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		writer.write_all(&self.content)
+	}
+}
+
+impl Findings for Merged {
+	fn findings(&self) -> usize {
+		self.findings
+	}
+}
+
+/// Merges JUnit reports, the name of each test suite is suffixed with its origin and the suites are numbered anew.
+#[derive(Debug, Default)]
+pub struct MergeJunit {
+	suites:   Vec<u8>,
+	id:       usize,
+	findings: usize
+}
+
+impl Converter for MergeJunit {
+	type Input  = Source;
+	type Output = Merged;
+	
+	fn push(&mut self, input: Self::Input) -> Result<(), Error> {
+		let mut reader = quick_xml::Reader::from_str(&input.content);
+		let mut writer = quick_xml::Writer::new(Vec::new());
+		let mut buf    = Vec::new();
+		let mut depth  = 0usize;
+		let xml_error  = |e: quick_xml::Error| Error::convert(format!("invalid JUnit report: {}", e));
+		
+		loop {
+			let event = reader.read_event(&mut buf).map_err(xml_error)?;
+			
+			match event {
+				// the declaration and the root element are written once for the merged report
+				Event::Decl(_) | Event::DocType(_) => (),
+				_ if depth == 0 && is_testsuites(&event) => (),
+				Event::Start(ref e) | Event::Empty(ref e) if e.name() == b"testsuite" => {
+					let empty = matches!(event, Event::Empty(_));
+					
+					if depth == 0 {
+						let suite = self.tag_suite(e, &input.origin).map_err(xml_error)?;
+						writer.write_event(if empty { Event::Empty(suite) } else { Event::Start(suite) }).map_err(xml_error)?;
+					} else {
+						writer.write_event(&event).map_err(xml_error)?;
+					}
+					
+					if !empty {
+						depth += 1;
+					}
+				}
+				Event::End(ref e) if e.name() == b"testsuite" => {
+					depth = depth.saturating_sub(1);
+					writer.write_event(&event).map_err(xml_error)?;
+				}
+				Event::Eof => break,
+				event => writer.write_event(&event).map_err(xml_error)?
+			}
+			
+			buf.clear();
+		}
+		
+		self.suites.extend(writer.into_inner());
+		Ok(())
+	}
+	
+	fn finish(self) -> Result<Self::Output, Error> {
+		let mut content = Vec::new();
+		content.extend_from_slice(b"<?xml version=\"1.0\" encoding=\"utf-8\"?><testsuites>");
+		content.extend_from_slice(&self.suites);
+		content.extend_from_slice(b"</testsuites>");
+		Ok(Merged { content, findings: self.findings })
+	}
+}
+
+impl MergeJunit {
+	fn tag_suite(&mut self, suite: &BytesStart, origin: &str) -> Result<BytesStart<'static>, quick_xml::Error> {
+		let mut tagged = BytesStart::owned_name(b"testsuite".to_vec());
+		
+		for attr in suite.attributes() {
+			let attr = attr?;
+			
+			match attr.key {
+				b"id" => tagged.push_attribute(("id", self.id.to_string().as_str())),
+				b"name" => {
+					let name = String::from_utf8_lossy(&attr.unescaped_value()?).into_owned();
+					tagged.push_attribute(("name", format!("{} [{}]", name, origin).as_str()));
+				}
+				b"failures" | b"errors" => {
+					self.findings += String::from_utf8_lossy(&attr.unescaped_value()?).trim().parse::<usize>().unwrap_or(0);
+					tagged.push_attribute(attr);
+				}
+				_ => tagged.push_attribute(attr)
+			}
+		}
+		
+		self.id += 1;
+		Ok(tagged)
+	}
+}
+
+fn is_testsuites(event: &Event) -> bool {
+	match event {
+		Event::Start(e) | Event::Empty(e) => e.name() == b"testsuites",
+		Event::End(e)                     => e.name() == b"testsuites",
+		_               => false
+	}
+}
+
+/// Merges code quality reports, identical issues of different reports are deduplicated by their fingerprint.
+///
+/// The description of each issue that does not occur in all reports is suffixed with the origins it occurs in.
+#[derive(Debug, Default)]
+pub struct MergeCodeQuality {
+	issues:  Vec<(Value, Vec<String>)>,
+	known:   HashMap<String, Vec<usize>>,
+	origins: usize
+}
+
+impl Converter for MergeCodeQuality {
+	type Input  = Source;
+	type Output = Merged;
+	
+	fn push(&mut self, input: Self::Input) -> Result<(), Error> {
+		let issues: Vec<Value> = read_document(input.content.as_bytes())?;
+		self.origins += 1;
+		
+		let mut matched = std::collections::HashSet::new();
+		
+		for issue in issues {
+			// issues without a fingerprint are only deduplicated if they are identical, fingerprints may only
+			// identify the message of an issue, not its location
+			let key = match issue.get("fingerprint").and_then(Value::as_str) {
+				Some(fingerprint) => format!("{}:{}", fingerprint, issue.get("location").unwrap_or(&Value::Null)),
+				None              => issue.to_string()
+			};
+			
+			// issues with the same fingerprint within a report, e.g. of a lint at different locations, are distinct
+			let known = self.known.entry(key).or_default();
+			
+			match known.iter().copied().find(|i| !matched.contains(i)) {
+				Some(i) => {
+					self.issues[i].1.push(input.origin.clone());
+					matched.insert(i);
+				}
+				None => {
+					known.push(self.issues.len());
+					matched.insert(self.issues.len());
+					self.issues.push((issue, vec![input.origin.clone()]));
+				}
+			}
+		}
+		
+		Ok(())
+	}
+	
+	fn finish(self) -> Result<Self::Output, Error> {
+		let origins = self.origins;
+		let issues  = self.issues.into_iter()
+			.map(|(mut issue, mut found_in)| {
+				found_in.dedup();
+				
+				if found_in.len() < origins {
+					if let Some(Value::String(description)) = issue.get_mut("description") {
+						description.push_str(&format!(" [{}]", found_in.join(", ")));
+					}
+				}
+				
+				issue
+			})
+			.collect::<Vec<_>>();
+		
+		Ok(Merged { findings: issues.len(), content: serde_json::to_vec(&issues).map_err(|e| Error::convert(e.to_string()))? })
+	}
+}
+
+/// Merges GitLab security reports, identical vulnerabilities are deduplicated by their identifiers.
+///
+/// The description of each vulnerability that does not occur in all reports is suffixed with the origins it
+/// occurs in. The other fields are taken from the first report, the scan spans from the earliest start to the latest end
+/// and fails if any of the scans failed.
+#[derive(Debug, Default)]
+pub struct MergeSecurityReport {
+	report:          Option<Value>,
+	vulnerabilities: Vec<(Value, Vec<String>)>,
+	known:           HashMap<String, usize>,
+	origins:         usize
+}
+
+impl Converter for MergeSecurityReport {
+	type Input  = Source;
+	type Output = Merged;
+	
+	fn push(&mut self, input: Self::Input) -> Result<(), Error> {
+		let mut report: Value = read_document(input.content.as_bytes())?;
+		
+		if !report.is_object() {
+			return Err(Error::convert("invalid security report, expected an object"));
+		}
+		
+		self.origins += 1;
+		
+		let vulnerabilities = take_array(&mut report, "vulnerabilities");
+		let remediations    = take_array(&mut report, "remediations");
+		let files           = take_array(&mut report, "dependency_files");
+		let scan            = report.get_mut("scan").map(Value::take);
+		let merged          = self.report.get_or_insert_with(|| {
+			let mut merged = report;
+			merged["vulnerabilities"]  = Value::Array(Vec::new());
+			merged["remediations"]     = Value::Array(Vec::new());
+			merged["dependency_files"] = Value::Array(Vec::new());
+			merged
+		});
+		
+		for vulnerability in vulnerabilities {
+			let key = vulnerability_key(&vulnerability);
+			
+			match self.known.get(&key) {
+				Some(&i) => self.vulnerabilities[i].1.push(input.origin.clone()),
+				None     => {
+					self.known.insert(key, self.vulnerabilities.len());
+					self.vulnerabilities.push((vulnerability, vec![input.origin.clone()]));
+				}
+			}
+		}
+		
+		for value in remediations {
+			if !merged["remediations"].as_array().is_some_and(|v| v.contains(&value)) {
+				push_array(merged, "remediations", value);
+			}
+		}
+		
+		for value in files {
+			if !merged["dependency_files"].as_array().is_some_and(|v| v.contains(&value)) {
+				push_array(merged, "dependency_files", value);
+			}
+		}
+		
+		match (merged.get_mut("scan"), scan) {
+			(Some(merged @ Value::Object(_)), Some(scan)) => merge_scan(merged, &scan),
+			(_, Some(scan)) => merged["scan"] = scan,
+			_ => ()
+		}
+		
+		Ok(())
+	}
+	
+	fn finish(self) -> Result<Self::Output, Error> {
+		let origins    = self.origins;
+		let mut report = self.report.unwrap_or_default();
+		let findings   = self.vulnerabilities.len();
+		
+		for (mut vulnerability, mut found_in) in self.vulnerabilities {
+			found_in.dedup();
+			
+			if found_in.len() < origins {
+				let tag = format!("[{}]", found_in.join(", "));
+				
+				match vulnerability.get_mut("description") {
+					Some(Value::String(description)) => description.push_str(&format!(" {}", tag)),
+					_ => if let Some(vulnerability) = vulnerability.as_object_mut() {
+						vulnerability.insert("description".to_string(), Value::String(tag));
+					}
+				}
+			}
+			
+			push_array(&mut report, "vulnerabilities", vulnerability);
+		}
+		
+		Ok(Merged { findings, content: serde_json::to_vec(&report).map_err(|e| Error::convert(e.to_string()))? })
+	}
+}
+
+/// Returns the identifiers of a vulnerability, or the whole vulnerability if it has none.
+fn vulnerability_key(vulnerability: &Value) -> String {
+	match vulnerability.get("identifiers").and_then(Value::as_array) {
+		Some(identifiers) if !identifiers.is_empty() => identifiers.iter()
+			.map(|v| format!("{}:{}", v["type"].as_str().unwrap_or_default(), v["value"].as_str().unwrap_or_default()))
+			.collect::<Vec<_>>()
+			.join(","),
+		_ => vulnerability.to_string()
+	}
+}
+
+fn merge_scan(merged: &mut Value, scan: &Value) {
+	// ISO 8601 timestamps in the same format compare like strings
+	if let (Some(a), Some(b)) = (merged["start_time"].as_str(), scan["start_time"].as_str()) {
+		if b < a {
+			merged["start_time"] = Value::String(b.to_string());
+		}
+	}
+	
+	if let (Some(a), Some(b)) = (merged["end_time"].as_str(), scan["end_time"].as_str()) {
+		if b > a {
+			merged["end_time"] = Value::String(b.to_string());
+		}
+	}
+	
+	if scan["status"] == "failure" {
+		merged["status"] = Value::String("failure".to_string());
+	}
+}
+
+fn take_array(value: &mut Value, key: &str) -> Vec<Value> {
+	match value.get_mut(key).map(Value::take) {
+		Some(Value::Array(values)) => values,
+		_                          => Vec::new()
+	}
+}
+
+fn push_array(value: &mut Value, key: &str, item: Value) {
+	if let Some(values) = value[key].as_array_mut() {
+		values.push(item);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn merge<C: Converter<Input = Source, Output = Merged> + Default>(sources: &[(&str, &str)]) -> Merged {
+		C::default()
+			.convert(sources.iter().map(|(origin, content)| Source { origin: origin.to_string(), content: content.to_string() }))
+			.unwrap()
+	}
+	
+	fn json(merged: &Merged) -> Value {
+		serde_json::from_slice(&merged.content).unwrap()
+	}
+	
+	#[test]
+	fn merge_junit() {
+		let merged = merge::<MergeJunit>(&[
+			("stable", concat!(
+				r#"<?xml version="1.0" encoding="utf-8"?><testsuites>"#,
+				r#"<testsuite id="0" name="my-crate::lib (unittests)" tests="3" failures="1" errors="1"><testcase name="parse"/></testsuite>"#,
+				r#"</testsuites>"#)),
+			("beta", r#"<?xml version="1.0" encoding="utf-8"?><testsuites/>"#),
+			("nightly", r#"<testsuites><testsuite id="0" name="my-crate (doctests)" tests="1" failures="1" errors="0"/></testsuites>"#)
+		]);
+		
+		assert_eq!(std::str::from_utf8(&merged.content).unwrap(), concat!(
+			r#"<?xml version="1.0" encoding="utf-8"?><testsuites>"#,
+			r#"<testsuite id="0" name="my-crate::lib (unittests) [stable]" tests="3" failures="1" errors="1"><testcase name="parse"/></testsuite>"#,
+			r#"<testsuite id="1" name="my-crate (doctests) [nightly]" tests="1" failures="1" errors="0"/>"#,
+			r#"</testsuites>"#));
+		assert_eq!(merged.findings, 3);
+	}
+	
+	#[test]
+	fn merge_code_quality() {
+		let merged = merge::<MergeCodeQuality>(&[
+			("stable", r#"[
+				{"description": "unused variable", "fingerprint": "a", "location": {"path": "src/lib.rs", "lines": {"begin": 1}}},
+				{"description": "unused variable", "fingerprint": "a", "location": {"path": "src/lib.rs", "lines": {"begin": 2}}}
+			]"#),
+			("nightly", r#"[
+				{"description": "unused variable", "fingerprint": "a", "location": {"path": "src/lib.rs", "lines": {"begin": 1}}},
+				{"description": "needless return", "fingerprint": "b", "location": {"path": "src/lib.rs", "lines": {"begin": 3}}}
+			]"#)
+		]);
+		
+		let descriptions = json(&merged).as_array().unwrap().iter()
+			.map(|v| v["description"].as_str().unwrap().to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(descriptions, ["unused variable", "unused variable [stable]", "needless return [nightly]"]);
+		assert_eq!(merged.findings, 3);
+	}
+	
+	#[test]
+	fn merge_security_report() {
+		let merged = merge::<MergeSecurityReport>(&[
+			("stable", r#"{"version": "2.0", "vulnerabilities": [
+				{"name": "RUSTSEC-2020-0071", "identifiers": [{"type": "RUSTSEC Advisory", "value": "RUSTSEC-2020-0071"}]},
+				{"name": "RUSTSEC-2021-0001", "description": "unsound", "identifiers": [{"type": "RUSTSEC Advisory", "value": "RUSTSEC-2021-0001"}]}
+			]}"#),
+			("nightly", r#"{"version": "2.0", "vulnerabilities": [
+				{"name": "time", "identifiers": [{"type": "RUSTSEC Advisory", "value": "RUSTSEC-2020-0071"}]}
+			]}"#)
+		]);
+		
+		let report = json(&merged);
+		let vulnerabilities = report["vulnerabilities"].as_array().unwrap();
+		assert_eq!(vulnerabilities.len(), 2);
+		assert_eq!(vulnerabilities[0]["name"], "RUSTSEC-2020-0071");
+		assert!(vulnerabilities[0].get("description").is_none());
+		assert_eq!(vulnerabilities[1]["description"], "unsound [stable]");
+		assert_eq!(merged.findings, 2);
+	}
+}
@@ -0,0 +1,310 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! OpenMetrics
+//!
+//! https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md
+
+use super::*;
+
+/// The metric families of a report, written in the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct Report(pub Vec<MetricFamily>);
+
+impl Report {
+	/// Returns the family with a name, it is added with the type and help text if it does not exist yet.
+	pub fn family(&mut self, name: &str, r#type: MetricType, help: &str) -> &mut MetricFamily {
+		let name = sanitize_name(name);
+		
+		match self.0.iter().position(|family| family.name == name) {
+			Some(i) => &mut self.0[i],
+			None    => {
+				self.0.push(MetricFamily { name, r#type, help: help.to_string(), ..Default::default() });
+				self.0.last_mut().expect("family was just added")
+			}
+		}
+	}
+}
+
+/// Metrics of the same name and type, which differ in their labels.
+#[derive(Clone, Debug, Default)]
+pub struct MetricFamily {
+	/// The name of the family, without the suffixes of the samples, like `_total`, but with the unit.
+	pub name:    String,
+	pub r#type:  MetricType,
+	pub help:    String,
+	pub unit:    Option<String>,
+	pub metrics: Vec<Metric>
+}
+
+impl MetricFamily {
+	/// Sets the unit, the name of the family must end with it, e.g. `exec_time_seconds`, and is suffixed with it
+	/// otherwise, so the family should be looked up by the suffixed name.
+	pub fn with_unit(&mut self, unit: &str) -> &mut Self {
+		if !self.name.ends_with(&format!("_{}", unit)) {
+			self.name = format!("{}_{}", self.name, unit);
+		}
+		
+		self.unit = Some(unit.to_string());
+		self
+	}
+	
+	/// Adds a metric with a single value, such as a counter or a gauge.
+	pub fn push(&mut self, labels: Vec<(String, String)>, value: f64) {
+		self.metrics.push(Metric { labels, value: Value::Number(value) });
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum MetricType {
+	Counter,
+	Gauge,
+	Histogram,
+	#[default]
+	Unknown
+}
+
+impl MetricType {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Counter   => "counter",
+			Self::Gauge     => "gauge",
+			Self::Histogram => "histogram",
+			Self::Unknown   => "unknown"
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Metric {
+	pub labels: Vec<(String, String)>,
+	pub value:  Value
+}
+
+#[derive(Clone, Debug)]
+pub enum Value {
+	Number(f64),
+	Histogram(Histogram)
+}
+
+/// The distribution of observations, the `+Inf` bucket is implied by the count.
+#[derive(Clone, Debug, Default)]
+pub struct Histogram {
+	/// The upper bounds of the buckets and the number of observations less than or equal to them.
+	pub buckets: Vec<(f64, u64)>,
+	pub sum:     f64,
+	pub count:   u64
+}
+
+impl Histogram {
+	/// Creates an empty histogram with buckets of the upper bounds.
+	pub fn new(bounds: &[f64]) -> Self {
+		Self { buckets: bounds.iter().map(|&bound| (bound, 0)).collect(), ..Default::default() }
+	}
+	
+	pub fn observe(&mut self, value: f64) {
+		for (bound, count) in &mut self.buckets {
+			if value <= *bound {
+				*count += 1;
+			}
+		}
+		
+		self.sum   += value;
+		self.count += 1;
+	}
+}
+
+impl WriteReport for Report {
+	fn write_report(&self, writer: &mut dyn io::Write) -> io::Result<()> {
+		for family in &self.0 {
+			writeln!(writer, "# TYPE {} {}", family.name, family.r#type.name())?;
+			
+			if let Some(unit) = &family.unit {
+				writeln!(writer, "# UNIT {} {}", family.name, unit)?;
+			}
+			
+			if !family.help.is_empty() {
+				writeln!(writer, "# HELP {} {}", family.name, escape(&family.help))?;
+			}
+			
+			for metric in &family.metrics {
+				match &metric.value {
+					Value::Number(value) => {
+						let suffix = match family.r#type {
+							MetricType::Counter => "_total",
+							_                   => ""
+						};
+						writeln!(writer, "{}{}{} {}", family.name, suffix, labels(&metric.labels, None), number(*value))?;
+					}
+					Value::Histogram(histogram) => {
+						for (bound, count) in &histogram.buckets {
+							writeln!(writer, "{}_bucket{} {}", family.name, labels(&metric.labels, Some(&float(*bound))), count)?;
+						}
+						
+						writeln!(writer, "{}_bucket{} {}", family.name, labels(&metric.labels, Some("+Inf")), histogram.count)?;
+						writeln!(writer, "{}_sum{} {}", family.name, labels(&metric.labels, None), number(histogram.sum))?;
+						writeln!(writer, "{}_count{} {}", family.name, labels(&metric.labels, None), histogram.count)?;
+					}
+				}
+			}
+		}
+		
+		writeln!(writer, "# EOF")
+	}
+}
+
+/// Metrics are not findings, a metrics report never has any.
+impl Findings for Report {
+	fn findings(&self) -> usize {
+		0
+	}
+}
+
+/// Replaces the characters that are not allowed in metric names, which are `[a-zA-Z_][a-zA-Z0-9_]*`
+/// without the colons that are reserved for recording rules, e.g. `tests::bench-add` becomes `tests__bench_add`.
+pub fn sanitize_name(name: &str) -> String {
+	let mut out = name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+		.collect::<String>();
+	
+	if !out.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+		out.insert(0, '_');
+	}
+	
+	out
+}
+
+/// Writes the label set of a metric, with the `le` label of a histogram bucket.
+fn labels(labels: &[(String, String)], le: Option<&str>) -> String {
+	let labels = labels.iter()
+		.map(|(name, value)| (sanitize_name(name), value.as_str()))
+		.chain(le.map(|le| ("le".to_string(), le)))
+		.map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+		.collect::<Vec<_>>();
+	
+	match labels.is_empty() {
+		true  => String::new(),
+		false => format!("{{{}}}", labels.join(","))
+	}
+}
+
+/// Escapes label values and help texts.
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Formats a value, integers without a fraction.
+fn number(value: f64) -> String {
+	match value {
+		v if v.is_nan()                 => "NaN".to_string(),
+		v if v.is_infinite() && v > 0.0 => "+Inf".to_string(),
+		v if v.is_infinite()            => "-Inf".to_string(),
+		v                               => v.to_string()
+	}
+}
+
+/// Formats a float, as the `le` label requires, e.g. `1.0` instead of `1`.
+fn float(value: f64) -> String {
+	match value.is_finite() {
+		true  => format!("{:?}", value),
+		false => number(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	
+	fn write(report: &Report) -> String {
+		let mut out = Vec::new();
+		report.write_report(&mut out).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+	
+	#[test]
+	fn escape_label_values_and_help() {
+		let mut report = Report::default();
+		report.family("passed", MetricType::Gauge, "The \"passed\"\ntests, C:\\")
+			.push(vec![("suite".to_string(), "a \"b\"\\c\nd".to_string())], 1.0);
+		
+		assert_eq!(write(&report), concat!(
+			"# TYPE passed gauge\n",
+			"# HELP passed The \\\"passed\\\"\\ntests, C:\\\\\n",
+			"passed{suite=\"a \\\"b\\\"\\\\c\\nd\"} 1\n",
+			"# EOF\n"));
+	}
+	
+	#[test]
+	fn sanitize_names() {
+		assert_eq!(sanitize_name("tests::bench-add"), "tests__bench_add");
+		assert_eq!(sanitize_name("1st"), "_1st");
+		assert_eq!(sanitize_name("exec_time_seconds"), "exec_time_seconds");
+		
+		let mut report = Report::default();
+		report.family("x", MetricType::Gauge, "").push(vec![("crate-name".to_string(), "a".to_string())], 0.5);
+		assert_eq!(write(&report), "# TYPE x gauge\nx{crate_name=\"a\"} 0.5\n# EOF\n");
+	}
+	
+	#[test]
+	fn counter_with_unit() {
+		let mut report = Report::default();
+		report.family("exec_time", MetricType::Counter, "Time.").with_unit("seconds").push(Vec::new(), 1.5);
+		report.family("exec_time_seconds", MetricType::Counter, "Time.").push(Vec::new(), f64::INFINITY);
+		
+		assert_eq!(report.0.len(), 1);
+		assert_eq!(write(&report), concat!(
+			"# TYPE exec_time_seconds counter\n",
+			"# UNIT exec_time_seconds seconds\n",
+			"# HELP exec_time_seconds Time.\n",
+			"exec_time_seconds_total 1.5\n",
+			"exec_time_seconds_total +Inf\n",
+			"# EOF\n"));
+	}
+	
+	#[test]
+	fn histogram() {
+		let mut histogram = Histogram::new(&[0.1, 1.0, 10.0]);
+		
+		for value in [0.05, 0.1, 0.5, 20.0] {
+			histogram.observe(value);
+		}
+		
+		assert_eq!(histogram.buckets, [(0.1, 2), (1.0, 3), (10.0, 3)]);
+		assert_eq!(histogram.count, 4);
+		
+		let mut report = Report::default();
+		report.family("duration_seconds", MetricType::Histogram, "").metrics.push(Metric {
+			labels: vec![("crate".to_string(), "a".to_string())],
+			value:  Value::Histogram(histogram)
+		});
+		
+		assert_eq!(write(&report), concat!(
+			"# TYPE duration_seconds histogram\n",
+			"duration_seconds_bucket{crate=\"a\",le=\"0.1\"} 2\n",
+			"duration_seconds_bucket{crate=\"a\",le=\"1.0\"} 3\n",
+			"duration_seconds_bucket{crate=\"a\",le=\"10.0\"} 3\n",
+			"duration_seconds_bucket{crate=\"a\",le=\"+Inf\"} 4\n",
+			"duration_seconds_sum{crate=\"a\"} 20.65\n",
+			"duration_seconds_count{crate=\"a\"} 4\n",
+			"# EOF\n"));
+	}
+}
@@ -0,0 +1,72 @@
+// MIT License
+//
+// Copyright (c) 2021 Tobias Pfeiffer
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Tests of the signal handling of the binary.
+
+#![cfg(unix)]
+
+use std::{io::Write, process::{Command, Stdio}, time::{Duration, Instant}};
+
+#[test]
+fn sigterm_during_detection() {
+	let path = std::env::temp_dir().join(format!("gitlab-report-sigterm-{}.xml", std::process::id()));
+	let mut child = Command::new(env!("CARGO_BIN_EXE_gitlab-report"))
+		.arg("-o")
+		.arg(&path)
+		.stdin(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.unwrap();
+	
+	// no suite finishes, so the input format is still being detected, while the input stays open
+	let mut stdin = child.stdin.take().unwrap();
+	stdin.write_all(concat!(
+		"{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 1 }\n",
+		"{ \"type\": \"test\", \"event\": \"started\", \"name\": \"tests::slow\" }\n"
+	).as_bytes()).unwrap();
+	stdin.flush().unwrap();
+	std::thread::sleep(Duration::from_millis(500));
+	
+	let status = Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+	assert!(status.success());
+	
+	let start  = Instant::now();
+	let status = loop {
+		if let Some(status) = child.try_wait().unwrap() {
+			break status;
+		}
+		
+		if start.elapsed() > Duration::from_secs(10) {
+			let _ = child.kill();
+			panic!("the process did not exit on SIGTERM");
+		}
+		
+		std::thread::sleep(Duration::from_millis(50));
+	};
+	
+	drop(stdin);
+	let report = std::fs::read_to_string(&path).unwrap();
+	let _ = std::fs::remove_file(&path);
+	assert_eq!(status.code(), Some(143));
+	assert!(report.contains(r#"<testcase name="slow" classname="tests""#), "{}", report);
+	assert!(report.contains(r#"<error type="killed""#), "{}", report);
+}